@@ -0,0 +1,646 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+use crate::{
+    conflicts::ConflictResolution,
+    data::{
+        addons::{AddonData, AddonProvider, ReleaseChannel},
+        LocalSaveData,
+    },
+    game::{self, ReleaseType},
+    installer,
+    inventory::{self, InstalledAddon},
+    wtf::{self, snapshots},
+    AppState,
+};
+
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// WAM's own export format, written as TOML.
+#[derive(Serialize, Deserialize)]
+pub struct AddonListExport {
+    pub format_version: u32,
+    pub release_type: ReleaseType,
+    pub addons: Vec<ExportedAddon>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportedAddon {
+    pub name: String,
+    pub provider: AddonProvider,
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Only exported for pinned addons, which should be installed at this exact version.
+    pub version_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub enum ImportFormat {
+    Wam,
+    WowUp,
+    CurseForge,
+}
+
+#[derive(Default, Serialize)]
+pub struct ImportReport {
+    pub format: Option<ImportFormat>,
+    /// Entries WAM already managed. Their channel and pin were updated from the file.
+    pub already_managed: Vec<String>,
+    /// Entries that were installed by hand and are managed by WAM now.
+    pub adopted: Vec<String>,
+    pub installed: Vec<String>,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+/// The entries of an imported list, and those that can't be imported from it at all.
+pub struct ParsedList {
+    pub format: ImportFormat,
+    pub entries: Vec<ExportedAddon>,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+#[derive(Serialize)]
+pub struct UnresolvedEntry {
+    pub name: String,
+    /// `None` when the list named a provider WAM doesn't support.
+    pub provider: Option<AddonProvider>,
+    pub project_id: Option<String>,
+    pub reason: String,
+}
+
+impl ExportedAddon {
+    fn from_addon_data(addon: &AddonData) -> Self {
+        Self {
+            name: addon.name.clone(),
+            provider: addon.provider,
+            project_id: addon.project_id.clone(),
+            channel: addon.channel,
+            pinned: addon.pinned,
+            version_id: addon.pinned.then(|| addon.version_id.clone()),
+        }
+    }
+
+    fn to_addon_data(&self) -> AddonData {
+        AddonData {
+            name: self.name.clone(),
+            version_id: self.version_id.clone().unwrap_or_default(),
+            version_name: None,
+            provider: self.provider,
+            project_id: self.project_id.clone(),
+            folders: Vec::new(),
+            channel: self.channel,
+            pinned: self.pinned,
+            settings_snapshots: Vec::new(),
+        }
+    }
+}
+
+pub fn export(release_type: ReleaseType, managed: &[AddonData]) -> AddonListExport {
+    AddonListExport {
+        format_version: EXPORT_FORMAT_VERSION,
+        release_type,
+        addons: managed.iter().map(ExportedAddon::from_addon_data).collect(),
+    }
+}
+
+/// Reads a WAM export, a WowUp export (plain or base64 encoded JSON) or a CurseForge
+/// `manifest.json`.
+pub fn parse(contents: &str) -> Result<ParsedList, String> {
+    let contents = contents.trim().trim_start_matches('\u{feff}');
+
+    if let Ok(export) = toml::from_str::<AddonListExport>(contents) {
+        if export.format_version > EXPORT_FORMAT_VERSION {
+            return Err(format!(
+                "the list was exported by a newer version of WAM (format {})",
+                export.format_version
+            ));
+        }
+        return Ok(ParsedList {
+            format: ImportFormat::Wam,
+            entries: export.addons,
+            unresolved: Vec::new(),
+        });
+    }
+
+    let json = match serde_json::from_str::<Value>(contents) {
+        Ok(json) => json,
+        Err(_) => {
+            let decoded = STANDARD
+                .decode(contents)
+                .map_err(|_| String::from("the file is not in a supported format"))?;
+            serde_json::from_slice(&decoded)
+                .map_err(|_| String::from("the file is not in a supported format"))?
+        }
+    };
+
+    let (format, results): (ImportFormat, Vec<Result<ExportedAddon, UnresolvedEntry>>) =
+        if let Some(addons) = json.get("addons").and_then(Value::as_array) {
+            (
+                ImportFormat::WowUp,
+                addons.iter().map(parse_wowup).collect(),
+            )
+        } else if let Some(files) = json.get("files").and_then(Value::as_array) {
+            (
+                ImportFormat::CurseForge,
+                files.iter().map(parse_curseforge).collect(),
+            )
+        } else {
+            return Err(String::from("the file is not in a supported format"));
+        };
+
+    let mut list = ParsedList {
+        format,
+        entries: Vec::new(),
+        unresolved: Vec::new(),
+    };
+    for result in results {
+        match result {
+            Ok(entry) => list.entries.push(entry),
+            Err(unresolved) => list.unresolved.push(unresolved),
+        }
+    }
+    Ok(list)
+}
+
+/// Ids are strings or numbers depending on the exporter.
+fn get_id(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The entry's name, falling back to its id and then to `fallback`.
+fn get_name(entry: &Value, id_key: &str, fallback: &str) -> String {
+    entry
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .or_else(|| get_id(entry, id_key))
+        .unwrap_or_else(|| fallback.to_owned())
+}
+
+/// WowUp entries look like `{ "id": "1234", "name": "...", "providerName": "Curse" }`.
+fn parse_wowup(entry: &Value) -> Result<ExportedAddon, UnresolvedEntry> {
+    let unresolved = |provider, reason: String| UnresolvedEntry {
+        name: get_name(entry, "id", "unnamed addon"),
+        provider,
+        project_id: get_id(entry, "id"),
+        reason,
+    };
+
+    let provider_name = entry
+        .get("providerName")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let provider = match provider_name {
+        "Curse" | "CurseV2" | "CurseForge" => AddonProvider::CurseForge,
+        "Wago" => AddonProvider::Wago,
+        "WowInterface" => AddonProvider::WowInterface,
+        "" => return Err(unresolved(None, String::from("the entry has no provider"))),
+        _ => {
+            return Err(unresolved(
+                None,
+                format!("{provider_name} is not a provider WAM supports"),
+            ))
+        }
+    };
+    let Some(project_id) = get_id(entry, "id") else {
+        return Err(unresolved(
+            Some(provider),
+            String::from("the entry has no project id"),
+        ));
+    };
+
+    Ok(ExportedAddon {
+        name: get_name(entry, "id", &project_id),
+        provider,
+        project_id: Some(project_id),
+        channel: ReleaseChannel::Stable,
+        pinned: false,
+        version_id: None,
+    })
+}
+
+/// CurseForge manifest entries look like `{ "projectID": 1234, "fileID": 5678 }`.
+fn parse_curseforge(entry: &Value) -> Result<ExportedAddon, UnresolvedEntry> {
+    let Some(project_id) = get_id(entry, "projectID") else {
+        let name = match get_id(entry, "fileID") {
+            Some(file_id) => format!("file {file_id}"),
+            None => String::from("unnamed addon"),
+        };
+        return Err(UnresolvedEntry {
+            name: get_name(entry, "projectID", &name),
+            provider: Some(AddonProvider::CurseForge),
+            project_id: None,
+            reason: String::from("the entry has no project id"),
+        });
+    };
+
+    Ok(ExportedAddon {
+        name: get_name(entry, "projectID", &project_id),
+        provider: AddonProvider::CurseForge,
+        project_id: Some(project_id),
+        channel: ReleaseChannel::Stable,
+        pinned: false,
+        version_id: get_id(entry, "fileID"),
+    })
+}
+
+/// What importing an entry would do.
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum ImportAction {
+    /// WAM already manages the addon, only its channel and pin are updated.
+    UpdateSettings,
+    /// The addon was installed by hand into these folders and will be managed by WAM.
+    Adopt {
+        folders: Vec<String>,
+    },
+    /// The addon will be installed from WAM's archive cache.
+    InstallCached {
+        #[serde(skip)]
+        archive_path: PathBuf,
+        version_id: String,
+    },
+    Unresolved {
+        reason: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct PlannedImport {
+    pub entry: ExportedAddon,
+    pub action: ImportAction,
+}
+
+/// Works out how each entry can be resolved, from what is already on disk or from WAM's
+/// archive cache, without changing anything. An addon listed more than once is planned once.
+pub fn plan(
+    installed: &[InstalledAddon],
+    entries: &[ExportedAddon],
+    managed: &[AddonData],
+) -> Vec<PlannedImport> {
+    let mut planned: Vec<PlannedImport> = Vec::new();
+    for entry in entries {
+        let addon = entry.to_addon_data();
+        if planned
+            .iter()
+            .any(|other| other.entry.to_addon_data().is_same_addon(&addon))
+        {
+            continue;
+        }
+        planned.push(PlannedImport {
+            entry: entry.clone(),
+            action: plan_entry(installed, entry, managed),
+        });
+    }
+    planned
+}
+
+fn plan_entry(
+    installed: &[InstalledAddon],
+    entry: &ExportedAddon,
+    managed: &[AddonData],
+) -> ImportAction {
+    let addon = entry.to_addon_data();
+    if managed.iter().any(|other| other.is_same_addon(&addon)) {
+        return ImportAction::UpdateSettings;
+    }
+
+    if let Some(project_id) = &entry.project_id {
+        let folders: Vec<String> = installed
+            .iter()
+            .filter(|installed| {
+                installed
+                    .toc
+                    .get_provider_ids()
+                    .iter()
+                    .any(|(provider, id)| *provider == entry.provider && id == project_id)
+            })
+            .filter(|installed| {
+                !managed
+                    .iter()
+                    .any(|other| other.claims_folder(&installed.folder))
+            })
+            .map(|installed| installed.folder.clone())
+            .collect();
+        if !folders.is_empty() {
+            return ImportAction::Adopt { folders };
+        }
+    }
+
+    match (&entry.version_id, installer::find_cached_archive(&addon)) {
+        // A pinned version can only come from an archive of that exact version.
+        (Some(version_id), Some((archive_path, cached_version_id)))
+            if *version_id == cached_version_id =>
+        {
+            ImportAction::InstallCached {
+                archive_path,
+                version_id: cached_version_id,
+            }
+        }
+        (None, Some((archive_path, version_id))) => ImportAction::InstallCached {
+            archive_path,
+            version_id,
+        },
+        _ => ImportAction::Unresolved {
+            reason: String::from(
+                "not installed and no cached archive, download it from the provider",
+            ),
+        },
+    }
+}
+
+/// Adds every planned entry to `managed`. Entries that would need a download from their
+/// provider are reported as unresolved.
+pub fn import(
+    addons_path: &Path,
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    installed: &[InstalledAddon],
+    planned: Vec<PlannedImport>,
+    managed: &mut Vec<AddonData>,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for PlannedImport { entry, action } in planned {
+        let mut addon = entry.to_addon_data();
+
+        let reason = match action {
+            ImportAction::UpdateSettings => {
+                if let Some(existing) = managed.iter_mut().find(|other| other.is_same_addon(&addon))
+                {
+                    existing.channel = entry.channel;
+                    existing.pinned = entry.pinned;
+                }
+                report.already_managed.push(entry.name);
+                continue;
+            }
+            ImportAction::Adopt { folders } => {
+                addon.version_name = installed
+                    .iter()
+                    .find(|installed| installed.folder == folders[0])
+                    .and_then(|installed| installed.toc.get("Version"))
+                    .map(str::to_owned);
+                addon.folders = folders;
+                managed.push(addon);
+                report.adopted.push(entry.name);
+                continue;
+            }
+            ImportAction::InstallCached {
+                archive_path,
+                version_id,
+            } => {
+                addon.version_id = version_id;
+                match installer::install_archive(
+                    addons_path,
+                    wtf_path,
+                    release_type,
+                    &archive_path,
+                    addon,
+                    managed,
+                    ConflictResolution::Abort,
+                ) {
+                    Ok(_) => {
+                        report.installed.push(entry.name);
+                        continue;
+                    }
+                    Err(error) => error.to_string(),
+                }
+            }
+            ImportAction::Unresolved { reason } => reason,
+        };
+
+        report.unresolved.push(UnresolvedEntry {
+            name: entry.name,
+            provider: Some(entry.provider),
+            project_id: entry.project_id,
+            reason,
+        });
+    }
+
+    report
+}
+
+/// Plans the entries against the current state of `release_type` without changing anything.
+pub fn preview_entries(
+    state: &AppState,
+    release_type: ReleaseType,
+    entries: &[ExportedAddon],
+) -> Result<Vec<PlannedImport>, String> {
+    let installed = inventory::scan(
+        &game::get_configured_addons_path(state, release_type)?,
+        release_type,
+    );
+    let addons = state.addons.lock().map_err(|error| error.to_string())?;
+    Ok(plan(&installed, entries, addons.get_list(release_type)))
+}
+
+/// Imports the entries into `release_type` and saves the addon list.
+pub fn import_entries(
+    state: &AppState,
+    release_type: ReleaseType,
+    entries: &[ExportedAddon],
+) -> Result<ImportReport, String> {
+    wtf::ensure_game_closed()?;
+    let addons_path = game::get_configured_addons_path(state, release_type)?;
+    let wtf_path = wtf::get_configured_wtf_path(state, release_type)?;
+    let installed = inventory::scan(&addons_path, release_type);
+
+    let mut addons = state.addons.lock().map_err(|error| error.to_string())?;
+    let planned = plan(&installed, entries, addons.get_list(release_type));
+    let report = import(
+        &addons_path,
+        &wtf_path,
+        release_type,
+        &installed,
+        planned,
+        addons.get_list_mut(release_type),
+    );
+    addons.save().map_err(|error| error.to_string())?;
+    drop(addons);
+    snapshots::apply_configured_retention(state)?;
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn export_addon_list(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    path: String,
+) -> Result<(), String> {
+    let export = match state.addons.lock() {
+        Ok(addons) => export(release_type, addons.get_list(release_type)),
+        Err(error) => return Err(error.to_string()),
+    };
+    let string = toml::to_string_pretty(&export).map_err(|error| error.to_string())?;
+    fs::write(path, string).map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+pub fn import_addon_list(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    path: String,
+) -> Result<ImportReport, String> {
+    let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let list = parse(&contents)?;
+
+    let mut report = import_entries(&state, release_type, &list.entries)?;
+    report.format = Some(list.format);
+    report.unresolved.extend(list.unresolved);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::addons::{AddonData, AddonProvider, ReleaseChannel};
+
+    use super::{parse, plan, ExportedAddon, ImportAction, ImportFormat, ParsedList};
+
+    fn parse_fixture(contents: &str) -> ParsedList {
+        parse(contents).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    #[test]
+    fn wam() {
+        let list = parse_fixture(include_str!("fixtures/addon-list.wam.toml"));
+        let addons = &list.entries;
+
+        assert!(matches!(list.format, ImportFormat::Wam));
+        assert!(list.unresolved.is_empty());
+        assert_eq!(addons.len(), 2);
+        assert_eq!(addons[0].provider, AddonProvider::CurseForge);
+        assert_eq!(addons[0].project_id.as_deref(), Some("61284"));
+        assert_eq!(addons[0].channel, ReleaseChannel::Stable);
+        assert!(!addons[0].pinned);
+        assert_eq!(addons[1].channel, ReleaseChannel::Beta);
+        assert!(addons[1].pinned);
+        assert_eq!(addons[1].version_id.as_deref(), Some("5.12.0"));
+    }
+
+    #[test]
+    fn wam_from_a_newer_version() {
+        let contents = include_str!("fixtures/addon-list.wam.toml")
+            .replace("format_version = 1", "format_version = 999");
+        assert!(parse(&contents).is_err());
+    }
+
+    fn assert_wowup(list: &ParsedList) {
+        let addons = &list.entries;
+        assert!(matches!(list.format, ImportFormat::WowUp));
+        assert_eq!(addons.len(), 3);
+        assert_eq!(addons[0].name, "Details! Damage Meter");
+        assert_eq!(addons[0].provider, AddonProvider::CurseForge);
+        assert_eq!(addons[1].provider, AddonProvider::WowInterface);
+        assert_eq!(addons[1].project_id.as_deref(), Some("13501"));
+        assert_eq!(addons[2].provider, AddonProvider::Wago);
+        assert_eq!(addons[2].name, "VBNBxKx5");
+        assert!(addons.iter().all(|addon| addon.version_id.is_none()));
+
+        // GitHub isn't a provider WAM knows, so the entry is reported instead.
+        assert_eq!(list.unresolved.len(), 1);
+        assert_eq!(list.unresolved[0].name, "From GitHub");
+        assert_eq!(list.unresolved[0].provider, None);
+        assert_eq!(list.unresolved[0].project_id.as_deref(), Some("some/repo"));
+        assert!(list.unresolved[0].reason.contains("GitHub"));
+    }
+
+    #[test]
+    fn wowup() {
+        assert_wowup(&parse_fixture(include_str!(
+            "fixtures/addon-list.wowup.json"
+        )));
+    }
+
+    #[test]
+    fn wowup_base64() {
+        assert_wowup(&parse_fixture(include_str!(
+            "fixtures/addon-list.wowup.txt"
+        )));
+    }
+
+    #[test]
+    fn curseforge() {
+        let list = parse_fixture(include_str!("fixtures/addon-list.curseforge.json"));
+        let addons = &list.entries;
+
+        assert!(matches!(list.format, ImportFormat::CurseForge));
+        assert_eq!(addons.len(), 2);
+        assert_eq!(addons[0].project_id.as_deref(), Some("61284"));
+        assert_eq!(addons[0].name, "61284");
+        assert_eq!(addons[0].version_id.as_deref(), Some("5678901"));
+        assert_eq!(addons[1].name, "Deadly Boss Mods");
+        assert!(addons
+            .iter()
+            .all(|addon| addon.provider == AddonProvider::CurseForge));
+
+        // The last entry has no project id to install it from.
+        assert_eq!(list.unresolved.len(), 1);
+        assert_eq!(list.unresolved[0].name, "file 42");
+        assert_eq!(list.unresolved[0].provider, Some(AddonProvider::CurseForge));
+        assert_eq!(list.unresolved[0].project_id, None);
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        for contents in [
+            "",
+            "not an addon list",
+            "{ \"profiles\": [] }",
+            "[[addons]]\nname = \"Missing fields\"",
+            "bm90IGpzb24=",
+        ] {
+            assert!(parse(contents).is_err(), "{contents:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn duplicates_are_planned_once() {
+        let entry = |name: &str, project_id: &str| ExportedAddon {
+            name: name.to_owned(),
+            provider: AddonProvider::CurseForge,
+            project_id: Some(project_id.to_owned()),
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            version_id: None,
+        };
+        let managed = [AddonData {
+            name: String::from("Details! Damage Meter"),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: Some(String::from("61284")),
+            folders: vec![String::from("Details")],
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }];
+
+        let planned = plan(
+            &[],
+            &[
+                entry("Details! Damage Meter", "61284"),
+                entry("Details", "61284"),
+            ],
+            &managed,
+        );
+
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].entry.name, "Details! Damage Meter");
+        assert!(matches!(planned[0].action, ImportAction::UpdateSettings));
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::{Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+    addon_list::{self, ExportedAddon, ImportReport, PlannedImport},
+    data::addons::AddonData,
+    game::ReleaseType,
+    AppState,
+};
+
+/// Every pack string starts with this, followed by the format version.
+const PREFIX: &str = "!WAM:";
+const PACK_VERSION: u32 = 1;
+/// The largest a pack may inflate to. Packs are pasted by strangers, so a small string must not
+/// be able to expand into gigabytes.
+const MAX_PACK_SIZE: u64 = 1024 * 1024;
+
+/// The contents of a pack string, before compression.
+#[derive(Serialize, Deserialize)]
+pub struct AddonPack {
+    pub release_type: ReleaseType,
+    pub addons: Vec<ExportedAddon>,
+}
+
+#[derive(Serialize)]
+pub struct PackPreview {
+    pub release_type: ReleaseType,
+    pub addons: Vec<PlannedImport>,
+}
+
+/// Encodes the addons as `!WAM:<version>!<crc32>!<base64 of the deflated JSON>`.
+pub fn encode(release_type: ReleaseType, addons: &[AddonData]) -> Result<String, String> {
+    let pack = AddonPack {
+        release_type,
+        addons: addon_list::export(release_type, addons).addons,
+    };
+    let json = serde_json::to_vec(&pack).map_err(|error| error.to_string())?;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(&json)
+        .map_err(|error| error.to_string())?;
+    let compressed = encoder.finish().map_err(|error| error.to_string())?;
+
+    Ok(format!(
+        "{PREFIX}{PACK_VERSION}!{:08x}!{}",
+        crc32fast::hash(&json),
+        URL_SAFE_NO_PAD.encode(compressed)
+    ))
+}
+
+pub fn decode(string: &str) -> Result<AddonPack, String> {
+    let invalid = || String::from("this is not a WAM addon pack");
+
+    let rest = string.trim().strip_prefix(PREFIX).ok_or_else(invalid)?;
+    let mut parts = rest.splitn(3, '!');
+    let (Some(version), Some(checksum), Some(data)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+
+    let version: u32 = version.parse().map_err(|_| invalid())?;
+    if version > PACK_VERSION {
+        return Err(format!(
+            "the pack was made by a newer version of WAM (version {version})"
+        ));
+    }
+    let checksum = u32::from_str_radix(checksum, 16).map_err(|_| invalid())?;
+
+    // Whitespace sneaks in when strings are pasted through chat clients.
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    let compressed = URL_SAFE_NO_PAD.decode(data).map_err(|_| invalid())?;
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_PACK_SIZE + 1)
+        .read_to_end(&mut json)
+        .map_err(|_| invalid())?;
+    if json.len() as u64 > MAX_PACK_SIZE {
+        return Err(String::from("the pack is too large"));
+    }
+
+    if crc32fast::hash(&json) != checksum {
+        return Err(String::from(
+            "the pack is damaged, make sure it was copied completely",
+        ));
+    }
+    serde_json::from_slice(&json).map_err(|error| error.to_string())
+}
+
+/// Encodes the managed addons of `release_type`, or only the ones named in `names`.
+#[tauri::command]
+pub fn encode_addon_pack(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    names: Option<Vec<String>>,
+) -> Result<String, String> {
+    let addons = state.addons.lock().map_err(|error| error.to_string())?;
+    let selected: Vec<AddonData> = addons
+        .get_list(release_type)
+        .iter()
+        .filter(|addon| match &names {
+            Some(names) => names.contains(&addon.name),
+            None => true,
+        })
+        .cloned()
+        .collect();
+
+    encode(release_type, &selected)
+}
+
+/// Decodes a pack and shows what importing it into `release_type` would do.
+#[tauri::command]
+pub fn preview_addon_pack(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    pack: String,
+) -> Result<PackPreview, String> {
+    let pack = decode(&pack)?;
+    Ok(PackPreview {
+        release_type: pack.release_type,
+        addons: addon_list::preview_entries(&state, release_type, &pack.addons)?,
+    })
+}
+
+#[tauri::command]
+pub fn import_addon_pack(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    pack: String,
+) -> Result<ImportReport, String> {
+    let pack = decode(&pack)?;
+    addon_list::import_entries(&state, release_type, &pack.addons)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use flate2::{write::DeflateEncoder, Compression};
+
+    use crate::{
+        data::addons::{AddonData, AddonProvider, ReleaseChannel},
+        game::ReleaseType,
+    };
+
+    use super::{decode, encode, MAX_PACK_SIZE, PREFIX};
+
+    fn addon(name: &str, project_id: &str) -> AddonData {
+        AddonData {
+            name: name.to_owned(),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: Some(project_id.to_owned()),
+            folders: Vec::new(),
+            channel: ReleaseChannel::Beta,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    /// A pack string for `data` that is valid apart from whatever the caller changes.
+    fn pack_string(version: &str, data: &[u8]) -> String {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        format!(
+            "{PREFIX}{version}!{:08x}!{}",
+            crc32fast::hash(data),
+            URL_SAFE_NO_PAD.encode(encoder.finish().unwrap())
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let string = encode(
+            ReleaseType::Retail,
+            &[
+                addon("Details! Damage Meter", "61284"),
+                addon("Bagnon", "1592"),
+            ],
+        )
+        .unwrap();
+        let pack = decode(&format!("  {string}\n")).unwrap_or_else(|error| panic!("{error}"));
+
+        assert_eq!(pack.release_type, ReleaseType::Retail);
+        assert_eq!(pack.addons.len(), 2);
+        assert_eq!(pack.addons[0].name, "Details! Damage Meter");
+        assert_eq!(pack.addons[1].project_id.as_deref(), Some("1592"));
+        assert_eq!(pack.addons[1].channel, ReleaseChannel::Beta);
+    }
+
+    #[test]
+    fn pasted_whitespace_is_ignored() {
+        let string = encode(ReleaseType::Retail, &[addon("Bagnon", "1592")]).unwrap();
+        let (head, tail) = string.split_at(string.len() - 4);
+        assert!(decode(&format!("{head}\n {tail}")).is_ok());
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let string = encode(ReleaseType::Retail, &[addon("Bagnon", "1592")]).unwrap();
+        // `!WAM:1!<crc32>!<data>`
+        let mut parts: Vec<&str> = string.split('!').collect();
+        parts[2] = if parts[2] == "00000000" {
+            "00000001"
+        } else {
+            "00000000"
+        };
+
+        let error = decode(&parts.join("!")).err().unwrap();
+        assert!(error.contains("damaged"), "{error}");
+    }
+
+    #[test]
+    fn bad_prefix() {
+        let string = encode(ReleaseType::Retail, &[addon("Bagnon", "1592")]).unwrap();
+        for string in [
+            string.replacen(PREFIX, "!WA:", 1),
+            string.replacen(PREFIX, "", 1),
+            String::from("!WAM:1!"),
+            String::new(),
+        ] {
+            assert!(decode(&string).is_err(), "{string:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn newer_version() {
+        let string = pack_string("2", br#"{"release_type":"Retail","addons":[]}"#);
+
+        let error = decode(&string).err().unwrap();
+        assert!(error.contains("newer version"), "{error}");
+    }
+
+    #[test]
+    fn oversized_pack() {
+        let data = vec![b' '; MAX_PACK_SIZE as usize + 1];
+
+        let error = decode(&pack_string("1", &data)).err().unwrap();
+        assert!(error.contains("too large"), "{error}");
+    }
+}
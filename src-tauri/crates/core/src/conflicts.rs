@@ -0,0 +1,220 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{data::addons::AddonData, inventory::InstalledAddon};
+
+#[derive(Clone, Serialize)]
+pub struct FolderConflict {
+    pub folder: String,
+    pub kind: ConflictKind,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum ConflictKind {
+    /// More than one managed addon lists the folder.
+    SharedFolder { claimed_by: Vec<String> },
+    /// The folder exists but no managed addon lists it, e.g. it was installed by hand.
+    Unmanaged,
+    /// The folder's TOC belongs to a different project than the addon that manages it,
+    /// which happens when something else was copied over a managed install.
+    ReplacedOnDisk { claimed_by: String },
+}
+
+/// How an install should treat folders that already belong to something else.
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+pub enum ConflictResolution {
+    /// Refuse to install and report the conflicts.
+    #[default]
+    Abort,
+    /// Take the folders over, removing them from the addons that claimed them.
+    Replace,
+    /// Install everything except the conflicting folders.
+    KeepExisting,
+}
+
+/// Finds folders that are claimed twice or that no longer match their managed addon.
+pub fn find_conflicts(installed: &[InstalledAddon], managed: &[AddonData]) -> Vec<FolderConflict> {
+    let mut claims: BTreeMap<String, (String, Vec<&AddonData>)> = BTreeMap::new();
+    for addon in managed {
+        for folder in &addon.folders {
+            claims
+                .entry(folder.to_ascii_lowercase())
+                .or_insert_with(|| (folder.clone(), Vec::new()))
+                .1
+                .push(addon);
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (folder, owners) in claims.values() {
+        if owners.len() > 1 {
+            conflicts.push(FolderConflict {
+                folder: folder.clone(),
+                kind: ConflictKind::SharedFolder {
+                    claimed_by: owners.iter().map(|owner| owner.name.clone()).collect(),
+                },
+            });
+        }
+    }
+
+    for addon in installed {
+        let Some((_, owners)) = claims.get(&addon.folder.to_ascii_lowercase()) else {
+            continue;
+        };
+        let [owner] = owners.as_slice() else {
+            continue;
+        };
+        let Some(project_id) = &owner.project_id else {
+            continue;
+        };
+
+        let toc_id = addon
+            .toc
+            .get_provider_ids()
+            .into_iter()
+            .find(|(provider, _)| *provider == owner.provider);
+        if let Some((_, toc_id)) = toc_id {
+            if &toc_id != project_id {
+                conflicts.push(FolderConflict {
+                    folder: addon.folder.clone(),
+                    kind: ConflictKind::ReplacedOnDisk {
+                        claimed_by: owner.name.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Finds the folders `incoming` would overwrite that belong to another addon or to nobody.
+pub fn find_install_conflicts(
+    addons_path: &Path,
+    incoming: &AddonData,
+    folders: &[String],
+    managed: &[AddonData],
+) -> Vec<FolderConflict> {
+    let mut conflicts = Vec::new();
+
+    for folder in folders {
+        let owners: Vec<String> = managed
+            .iter()
+            .filter(|addon| !addon.is_same_addon(incoming) && addon.claims_folder(folder))
+            .map(|addon| addon.name.clone())
+            .collect();
+        let claimed_by_incoming = managed
+            .iter()
+            .any(|addon| addon.is_same_addon(incoming) && addon.claims_folder(folder));
+
+        if !owners.is_empty() {
+            conflicts.push(FolderConflict {
+                folder: folder.clone(),
+                kind: ConflictKind::SharedFolder { claimed_by: owners },
+            });
+        } else if !claimed_by_incoming && addons_path.join(folder).exists() {
+            conflicts.push(FolderConflict {
+                folder: folder.clone(),
+                kind: ConflictKind::Unmanaged,
+            });
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        data::addons::{AddonData, AddonProvider, ReleaseChannel},
+        inventory::InstalledAddon,
+        toc::Toc,
+    };
+
+    use super::{find_conflicts, find_install_conflicts, ConflictKind};
+
+    fn addon(name: &str, project_id: &str, folders: &[&str]) -> AddonData {
+        AddonData {
+            name: name.to_owned(),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: Some(project_id.to_owned()),
+            folders: folders.iter().map(|folder| folder.to_string()).collect(),
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    fn installed(folder: &str, toc: &str) -> InstalledAddon {
+        InstalledAddon {
+            folder: folder.to_owned(),
+            toc: Toc::parse(folder, toc),
+        }
+    }
+
+    #[test]
+    fn shared_folders() {
+        let managed = [
+            addon("ElvUI", "1", &["ElvUI", "ElvUI_Libraries"]),
+            addon("ElvUI Plugin", "2", &["ElvUI_Plugin", "elvui_libraries"]),
+        ];
+
+        let conflicts = find_conflicts(&[], &managed);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].folder, "ElvUI_Libraries");
+        assert!(matches!(
+            &conflicts[0].kind,
+            ConflictKind::SharedFolder { claimed_by } if claimed_by == &["ElvUI", "ElvUI Plugin"]
+        ));
+    }
+
+    #[test]
+    fn folders_replaced_on_disk() {
+        let managed = [addon("Details", "61284", &["Details"])];
+        let installed_addons = [installed("Details", "## X-Curse-Project-ID: 99999\n")];
+
+        let conflicts = find_conflicts(&installed_addons, &managed);
+
+        assert_eq!(conflicts.len(), 1);
+        assert!(matches!(
+            &conflicts[0].kind,
+            ConflictKind::ReplacedOnDisk { claimed_by } if claimed_by == "Details"
+        ));
+
+        let installed_addons = [installed("Details", "## X-Curse-Project-ID: 61284\n")];
+        assert!(find_conflicts(&installed_addons, &managed).is_empty());
+    }
+
+    #[test]
+    fn install_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["Plugin", "Manual", "Library"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+        }
+        let managed = [
+            addon("Plugin", "1", &["Plugin"]),
+            addon("Other", "2", &["Library"]),
+        ];
+        let folders = ["Plugin", "Manual", "Library", "New"].map(String::from);
+
+        let conflicts = find_install_conflicts(dir.path(), &managed[0], &folders, &managed);
+
+        let conflicts: Vec<(&str, &ConflictKind)> = conflicts
+            .iter()
+            .map(|conflict| (conflict.folder.as_str(), &conflict.kind))
+            .collect();
+        assert_eq!(conflicts.len(), 2);
+        assert!(matches!(conflicts[0], ("Manual", ConflictKind::Unmanaged)));
+        assert!(matches!(
+            conflicts[1],
+            ("Library", ConflictKind::SharedFolder { claimed_by }) if claimed_by == &["Other"]
+        ));
+    }
+}
@@ -1,24 +1,121 @@
 use confique::Config;
 use serde::{Deserialize, Serialize};
 
-use super::LocalSaveData;
+use crate::game::ReleaseType;
+
+use super::{
+    location::DataKind,
+    migrations::{self, Migration, MigrationError},
+    LocalSaveData,
+};
 
 #[derive(Config, Serialize)]
 pub struct Addons {
     retail_list: Vec<AddonData>,
 }
 
-#[derive(Deserialize, Serialize)]
-struct AddonData {
-    name: String,
-    version_id: String,
-    version_name: Option<String>,
-    provider: AddonProvider,
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AddonData {
+    pub name: String,
+    pub version_id: String,
+    pub version_name: Option<String>,
+    pub provider: AddonProvider,
+    /// The provider's id for this addon, if it is known.
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// The folders inside `Interface/AddOns` that were installed as part of this addon.
+    #[serde(default)]
+    pub folders: Vec<String>,
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    /// Pinned addons stay on their current version when updating.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Snapshots of the addon's SavedVariables taken before it was installed over or updated.
+    #[serde(default)]
+    pub settings_snapshots: Vec<SettingsSnapshot>,
+}
+
+/// How many settings snapshots an addon refers to. Older ones are left to snapshot retention.
+const MAX_SETTINGS_SNAPSHOTS: usize = 5;
+
+/// A WTF snapshot of an addon's settings, as they were while `version_id` was installed.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SettingsSnapshot {
+    pub snapshot_id: String,
+    /// `None` when the folders weren't managed by WAM yet.
+    pub version_id: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
-enum AddonProvider {
+/// The least stable kind of release an addon should be updated to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    Beta,
+    Alpha,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum AddonProvider {
     CurseForge,
+    Wago,
+    WowInterface,
+}
+
+impl AddonProvider {
+    /// The TOC metadata field that providers use to store their project id.
+    pub fn get_toc_id_field(&self) -> &'static str {
+        match self {
+            AddonProvider::CurseForge => "X-Curse-Project-ID",
+            AddonProvider::Wago => "X-Wago-ID",
+            AddonProvider::WowInterface => "X-WoWI-ID",
+        }
+    }
+}
+
+impl AddonData {
+    /// Whether `other` refers to the same project, e.g. an update of this addon.
+    pub fn is_same_addon(&self, other: &AddonData) -> bool {
+        self.provider == other.provider
+            && match (&self.project_id, &other.project_id) {
+                (Some(project_id), Some(other_project_id)) => project_id == other_project_id,
+                _ => self.name == other.name,
+            }
+    }
+
+    pub fn claims_folder(&self, folder: &str) -> bool {
+        self.folders
+            .iter()
+            .any(|claimed| claimed.eq_ignore_ascii_case(folder))
+    }
+
+    /// Records `snapshot` in place of an earlier one of the same version, dropping the oldest
+    /// once there are more than `MAX_SETTINGS_SNAPSHOTS`.
+    pub fn add_settings_snapshot(&mut self, snapshot: SettingsSnapshot) {
+        self.settings_snapshots
+            .retain(|other| other.version_id != snapshot.version_id);
+        self.settings_snapshots.push(snapshot);
+        let excess = self
+            .settings_snapshots
+            .len()
+            .saturating_sub(MAX_SETTINGS_SNAPSHOTS);
+        self.settings_snapshots.drain(..excess);
+    }
+}
+
+impl Addons {
+    pub fn get_list(&self, release_type: ReleaseType) -> &Vec<AddonData> {
+        match release_type {
+            ReleaseType::Retail => &self.retail_list,
+        }
+    }
+
+    pub fn get_list_mut(&mut self, release_type: ReleaseType) -> &mut Vec<AddonData> {
+        match release_type {
+            ReleaseType::Retail => &mut self.retail_list,
+        }
+    }
 }
 
 impl LocalSaveData for Addons {
@@ -31,4 +128,105 @@ impl LocalSaveData for Addons {
     fn get_file_name() -> String {
         String::from("addons.toml")
     }
+
+    fn get_data_kind() -> DataKind {
+        DataKind::State
+    }
+
+    fn get_migrations() -> &'static [Migration] {
+        &[add_folders, add_channel_and_pin, add_settings_snapshots]
+    }
+}
+
+/// Version 1 records which folders each addon installed.
+fn add_folders(table: &mut toml::Table) -> Result<(), MigrationError> {
+    for addon in migrations::get_table_array(table, "retail_list")? {
+        addon
+            .entry("folders")
+            .or_insert_with(|| toml::Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+/// Version 2 adds the release channel and pin to every addon.
+fn add_channel_and_pin(table: &mut toml::Table) -> Result<(), MigrationError> {
+    for addon in migrations::get_table_array(table, "retail_list")? {
+        addon
+            .entry("channel")
+            .or_insert_with(|| toml::Value::String(String::from("Stable")));
+        addon
+            .entry("pinned")
+            .or_insert(toml::Value::Boolean(false));
+    }
+    Ok(())
+}
+
+/// Version 3 records the settings snapshots taken before installs and updates.
+fn add_settings_snapshots(table: &mut toml::Table) -> Result<(), MigrationError> {
+    for addon in migrations::get_table_array(table, "retail_list")? {
+        addon
+            .entry("settings_snapshots")
+            .or_insert_with(|| toml::Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddonData, AddonProvider, ReleaseChannel, SettingsSnapshot};
+
+    fn snapshot(snapshot_id: &str, version_id: Option<&str>) -> SettingsSnapshot {
+        SettingsSnapshot {
+            snapshot_id: snapshot_id.to_owned(),
+            version_id: version_id.map(str::to_owned),
+        }
+    }
+
+    fn snapshot_ids(addon: &AddonData) -> Vec<&str> {
+        addon
+            .settings_snapshots
+            .iter()
+            .map(|snapshot| snapshot.snapshot_id.as_str())
+            .collect()
+    }
+
+    fn addon() -> AddonData {
+        AddonData {
+            name: String::from("ElvUI"),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::WowInterface,
+            project_id: Some(String::from("1234")),
+            folders: vec![String::from("ElvUI")],
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn settings_snapshots_replace_the_same_version() {
+        let mut addon = addon();
+
+        addon.add_settings_snapshot(snapshot("100", None));
+        addon.add_settings_snapshot(snapshot("200", Some("1")));
+        addon.add_settings_snapshot(snapshot("300", Some("2")));
+        addon.add_settings_snapshot(snapshot("400", Some("1")));
+
+        assert_eq!(snapshot_ids(&addon), ["100", "300", "400"]);
+    }
+
+    #[test]
+    fn settings_snapshots_are_capped() {
+        let mut addon = addon();
+
+        for version in 1..=7 {
+            addon.add_settings_snapshot(snapshot(
+                &format!("{version}00"),
+                Some(&version.to_string()),
+            ));
+        }
+
+        assert_eq!(snapshot_ids(&addon), ["300", "400", "500", "600", "700"]);
+    }
 }
@@ -1,5 +1,5 @@
 use confique::Config;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tauri::State;
 
 use crate::{game::ReleaseType, AppState};
@@ -10,6 +10,12 @@ use super::LocalSaveData;
 pub struct AppConfig {
     #[config(nested)]
     game_paths: GamePaths,
+    #[config(nested)]
+    snapshots: SnapshotSettings,
+    #[config(default = "Block")]
+    running_game_policy: RunningGamePolicy,
+    #[config(nested)]
+    launch_commands: LaunchCommands,
 }
 
 #[derive(Config, Serialize)]
@@ -17,12 +23,54 @@ struct GamePaths {
     retail: Option<String>,
 }
 
+#[derive(Config, Serialize)]
+pub struct LaunchCommands {
+    retail: Option<LaunchCommand>,
+}
+
+/// How WAM starts a game client.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LaunchCommand {
+    /// A command line, e.g. `wine "/games/World of Warcraft/_retail_/Wow.exe"`, or a URI such
+    /// as `lutris:rungameid/3` or `steam://rungameid/<id>`, which is opened by the desktop.
+    pub command: String,
+    /// Appended to a command line, e.g. `-console` or `-launcherlogin`, or put into a Steam URI.
+    /// Lutris takes the game's arguments from its own settings instead.
+    #[serde(default)]
+    pub arguments: Vec<String>,
+}
+
+/// How many WTF snapshots are kept before the oldest are deleted.
+#[derive(Clone, Config, Serialize)]
+pub struct SnapshotSettings {
+    #[config(default = 20)]
+    pub max_count: usize,
+    /// Snapshots older than this many days are deleted, if set.
+    pub max_age_days: Option<u64>,
+}
+
+/// What happens to installs and WTF changes requested while the game is running.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RunningGamePolicy {
+    /// They fail, asking to close the game first.
+    #[default]
+    Block,
+    /// They are queued and run once the game exits.
+    Queue,
+}
+
 impl LocalSaveData for AppConfig {
     type Data = AppConfig;
 
     fn new() -> Self {
         Self {
             game_paths: GamePaths { retail: None },
+            snapshots: SnapshotSettings {
+                max_count: 20,
+                max_age_days: None,
+            },
+            running_game_policy: RunningGamePolicy::Block,
+            launch_commands: LaunchCommands { retail: None },
         }
     }
 
@@ -31,6 +79,12 @@ impl LocalSaveData for AppConfig {
     }
 }
 
+impl AppConfig {
+    pub fn get_snapshot_settings(&self) -> &SnapshotSettings {
+        &self.snapshots
+    }
+}
+
 #[wam_macros::contains_tauri_commands(
     body_state = "state.config",
     mutex_behavior = "match_to_option"
@@ -49,11 +103,43 @@ impl AppConfig {
             ReleaseType::Retail => self.game_paths.retail = Some(path),
         }
     }
+
+    #[wam_macros::with_tauri_command]
+    pub fn get_launch_command(&self, release_type: ReleaseType) -> &Option<LaunchCommand> {
+        match release_type {
+            ReleaseType::Retail => &self.launch_commands.retail,
+        }
+    }
+
+    #[wam_macros::with_tauri_command(mutable_mutex)]
+    pub fn set_launch_command(&mut self, release_type: ReleaseType, launch_command: LaunchCommand) {
+        match release_type {
+            ReleaseType::Retail => self.launch_commands.retail = Some(launch_command),
+        }
+    }
+
+    #[wam_macros::with_tauri_command(mutable_mutex)]
+    pub fn clear_launch_command(&mut self, release_type: ReleaseType) {
+        match release_type {
+            ReleaseType::Retail => self.launch_commands.retail = None,
+        }
+    }
+
+    #[wam_macros::with_tauri_command]
+    pub fn get_running_game_policy(&self) -> &RunningGamePolicy {
+        &self.running_game_policy
+    }
+
+    #[wam_macros::with_tauri_command(mutable_mutex)]
+    pub fn set_running_game_policy(&mut self, policy: RunningGamePolicy) {
+        self.running_game_policy = policy;
+    }
 }
 
 #[tauri::command]
-pub fn save_config(state: State<AppState>) {
-    if let Ok(config) = state.config.lock() {
-        config.save();
+pub fn save_config(state: State<AppState>) -> Result<(), String> {
+    match state.config.lock() {
+        Ok(config) => config.save().map_err(|error| error.to_string()),
+        Err(error) => Err(error.to_string()),
     }
 }
@@ -0,0 +1,317 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use platform_dirs::AppDirs;
+use serde::Serialize;
+
+/// A file next to the executable that makes WAM keep its data beside it.
+pub const PORTABLE_MARKER: &str = "wam-portable";
+pub const DATA_DIR_ENV: &str = "WAM_DATA_DIR";
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+
+/// Where WAM keeps its files, and why it picked that place.
+#[derive(Clone, Serialize)]
+pub struct DataLocation {
+    pub source: DataLocationSource,
+    /// User settings, e.g. `config.toml`.
+    pub config_dir: PathBuf,
+    /// Data WAM manages itself, e.g. `addons.toml`.
+    pub state_dir: PathBuf,
+    /// Files that can be downloaded or rebuilt again, e.g. addon archives.
+    pub cache_dir: PathBuf,
+    pub log_dir: PathBuf,
+    /// Where every file was kept before they were split by kind.
+    #[serde(skip)]
+    legacy_dir: PathBuf,
+}
+
+#[derive(Clone, Copy)]
+pub enum DataKind {
+    Config,
+    State,
+    Cache,
+    Logs,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub enum DataLocationSource {
+    CommandLine,
+    Environment,
+    Portable,
+    Platform,
+}
+
+impl DataLocation {
+    /// Splits a single folder (portable mode or an override) into one subfolder per kind.
+    fn from_root(root: PathBuf, source: DataLocationSource) -> Self {
+        Self {
+            source,
+            config_dir: root.join("config"),
+            state_dir: root.join("state"),
+            cache_dir: root.join("cache"),
+            log_dir: root.join("logs"),
+            legacy_dir: root,
+        }
+    }
+
+    fn from_platform(app_dirs: AppDirs) -> Self {
+        Self {
+            source: DataLocationSource::Platform,
+            log_dir: app_dirs.state_dir.join("logs"),
+            state_dir: app_dirs.state_dir,
+            cache_dir: app_dirs.cache_dir,
+            legacy_dir: app_dirs.config_dir.clone(),
+            config_dir: app_dirs.config_dir,
+        }
+    }
+
+    pub fn get_dir(&self, kind: DataKind) -> &Path {
+        match kind {
+            DataKind::Config => &self.config_dir,
+            DataKind::State => &self.state_dir,
+            DataKind::Cache => &self.cache_dir,
+            DataKind::Logs => &self.log_dir,
+        }
+    }
+
+    /// Moves `file` out of the old single folder layout if it hasn't been moved yet. A file
+    /// already in the new folder is never replaced.
+    pub fn migrate_legacy_file(&self, file: &str, kind: DataKind) -> io::Result<()> {
+        let legacy_path = self.legacy_dir.join(file);
+        let path = self.get_dir(kind).join(file);
+        if legacy_path == path || !legacy_path.is_file() || path.exists() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(self.get_dir(kind))?;
+        // A rename fails across file systems, which the platform folders may be on.
+        if fs::rename(&legacy_path, &path).is_err() {
+            fs::copy(&legacy_path, &path)?;
+            fs::remove_file(&legacy_path)?;
+        }
+        Ok(())
+    }
+}
+
+static DATA_LOCATION: OnceLock<Option<DataLocation>> = OnceLock::new();
+
+/// Resolves the data location once and returns it for the rest of the session.
+pub fn get() -> Option<&'static DataLocation> {
+    DATA_LOCATION
+        .get_or_init(|| {
+            let exe_dir = env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf));
+            resolve(env::args().skip(1), env::var_os(DATA_DIR_ENV), exe_dir)
+                .or_else(|| AppDirs::new(Some("wam1"), true).map(DataLocation::from_platform))
+        })
+        .as_ref()
+}
+
+/// The `--data-dir` flag in `args` wins over `WAM_DATA_DIR`, which wins over portable mode.
+/// Returns `None` when none of them is set, so the platform's folders are used.
+fn resolve(
+    args: impl Iterator<Item = String>,
+    env_dir: Option<OsString>,
+    exe_dir: Option<PathBuf>,
+) -> Option<DataLocation> {
+    if let Some(dir) = get_flag_value(args) {
+        return Some(DataLocation::from_root(
+            dir,
+            DataLocationSource::CommandLine,
+        ));
+    }
+
+    if let Some(dir) = env_dir.filter(|dir| !dir.is_empty()) {
+        return Some(DataLocation::from_root(
+            PathBuf::from(dir),
+            DataLocationSource::Environment,
+        ));
+    }
+
+    exe_dir
+        .filter(|exe_dir| exe_dir.join(PORTABLE_MARKER).is_file())
+        .map(|exe_dir| DataLocation::from_root(exe_dir, DataLocationSource::Portable))
+}
+
+/// Accepts both `--data-dir <path>` and `--data-dir=<path>`.
+fn get_flag_value(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg
+            .strip_prefix(DATA_DIR_FLAG)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+/// Returns the folder for `kind`, creating it if needed.
+pub fn get_or_create_dir(kind: DataKind) -> Option<PathBuf> {
+    let dir = get()?.get_dir(kind).to_path_buf();
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+#[tauri::command]
+pub fn get_data_location() -> Option<DataLocation> {
+    get().cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs, path::PathBuf};
+
+    use platform_dirs::AppDirs;
+
+    use super::{
+        get_flag_value, resolve, DataKind, DataLocation, DataLocationSource, PORTABLE_MARKER,
+    };
+
+    fn args<'a>(args: &'a [&str]) -> impl Iterator<Item = String> + 'a {
+        args.iter().map(|arg| arg.to_string())
+    }
+
+    #[test]
+    fn flag_values() {
+        assert_eq!(
+            get_flag_value(args(&["--data-dir", "/tmp/wam"])),
+            Some(PathBuf::from("/tmp/wam"))
+        );
+        assert_eq!(
+            get_flag_value(args(&["--minimized", "--data-dir=/tmp/wam"])),
+            Some(PathBuf::from("/tmp/wam"))
+        );
+        assert_eq!(get_flag_value(args(&["--data-dir"])), None);
+        assert_eq!(get_flag_value(args(&["--data-directory=/tmp/wam"])), None);
+        assert_eq!(get_flag_value(args(&[])), None);
+    }
+
+    #[test]
+    fn flag_wins_over_environment() {
+        let location = resolve(
+            args(&["--data-dir", "/tmp/flag"]),
+            Some(OsString::from("/tmp/env")),
+            None,
+        )
+        .unwrap();
+
+        assert!(matches!(location.source, DataLocationSource::CommandLine));
+        assert_eq!(location.config_dir, PathBuf::from("/tmp/flag/config"));
+        assert_eq!(location.state_dir, PathBuf::from("/tmp/flag/state"));
+    }
+
+    #[test]
+    fn environment_wins_over_portable_mode() {
+        let exe_dir = tempfile::tempdir().unwrap();
+        fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+
+        let location = resolve(
+            args(&[]),
+            Some(OsString::from("/tmp/env")),
+            Some(exe_dir.path().to_path_buf()),
+        )
+        .unwrap();
+
+        assert!(matches!(location.source, DataLocationSource::Environment));
+        assert_eq!(location.cache_dir, PathBuf::from("/tmp/env/cache"));
+    }
+
+    #[test]
+    fn portable_mode_needs_the_marker() {
+        let exe_dir = tempfile::tempdir().unwrap();
+        let exe_path = Some(exe_dir.path().to_path_buf());
+        assert!(resolve(args(&[]), Some(OsString::new()), exe_path.clone()).is_none());
+
+        fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+        let location = resolve(args(&[]), None, exe_path).unwrap();
+
+        assert!(matches!(location.source, DataLocationSource::Portable));
+        assert_eq!(location.log_dir, exe_dir.path().join("logs"));
+    }
+
+    #[test]
+    fn legacy_files_are_moved_by_kind() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("config.toml"), "config").unwrap();
+        fs::write(root.path().join("addons.toml"), "addons").unwrap();
+        let location =
+            DataLocation::from_root(root.path().to_path_buf(), DataLocationSource::Portable);
+
+        location
+            .migrate_legacy_file("config.toml", DataKind::Config)
+            .unwrap();
+        location
+            .migrate_legacy_file("addons.toml", DataKind::State)
+            .unwrap();
+
+        assert!(!root.path().join("config.toml").exists());
+        assert!(!root.path().join("addons.toml").exists());
+        assert_eq!(
+            fs::read_to_string(root.path().join("config/config.toml")).unwrap(),
+            "config"
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("state/addons.toml")).unwrap(),
+            "addons"
+        );
+    }
+
+    #[test]
+    fn migrated_files_are_not_replaced() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("state")).unwrap();
+        fs::write(root.path().join("addons.toml"), "old").unwrap();
+        fs::write(root.path().join("state/addons.toml"), "new").unwrap();
+        let location =
+            DataLocation::from_root(root.path().to_path_buf(), DataLocationSource::Portable);
+
+        location
+            .migrate_legacy_file("addons.toml", DataKind::State)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.path().join("addons.toml")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("state/addons.toml")).unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn files_already_in_place_are_left_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let location = DataLocation::from_platform(AppDirs {
+            cache_dir: root.path().join("cache"),
+            config_dir: root.path().join("config"),
+            data_dir: root.path().join("data"),
+            state_dir: root.path().join("state"),
+        });
+        fs::create_dir_all(root.path().join("config")).unwrap();
+        fs::write(root.path().join("config/config.toml"), "config").unwrap();
+
+        location
+            .migrate_legacy_file("config.toml", DataKind::Config)
+            .unwrap();
+        location
+            .migrate_legacy_file("missing.toml", DataKind::State)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.path().join("config/config.toml")).unwrap(),
+            "config"
+        );
+        assert!(!root.path().join("state").exists());
+    }
+}
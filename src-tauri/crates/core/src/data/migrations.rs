@@ -0,0 +1,258 @@
+use std::{fmt, fs, path::Path};
+
+use toml::{Table, Value};
+
+use super::{watcher, write_atomically};
+
+/// Upgrades a document from the schema version at its index to the next one.
+pub type Migration = fn(&mut Table) -> Result<(), MigrationError>;
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+pub enum MigrationError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    /// The file was written by a newer version of WAM.
+    NewerSchema {
+        found: i64,
+        supported: i64,
+    },
+    /// A migration step found something it can't upgrade.
+    Invalid(String),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Io(error) => write!(f, "{error}"),
+            MigrationError::Parse(error) => write!(f, "{error}"),
+            MigrationError::NewerSchema { found, supported } => write!(
+                f,
+                "schema version {found} is newer than the supported version {supported}"
+            ),
+            MigrationError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// The schema version stored in a document. Documents from before versioning are version 0.
+pub fn get_schema_version(table: &Table) -> i64 {
+    table
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(Value::as_integer)
+        .unwrap_or(0)
+}
+
+/// Runs every migration the document hasn't seen yet, returning whether anything changed.
+pub fn migrate(table: &mut Table, migrations: &[Migration]) -> Result<bool, MigrationError> {
+    let version = get_schema_version(table);
+    let supported = migrations.len() as i64;
+    if version > supported {
+        return Err(MigrationError::NewerSchema {
+            found: version,
+            supported,
+        });
+    }
+    if version == supported {
+        return Ok(false);
+    }
+
+    for migration in &migrations[version.max(0) as usize..] {
+        migration(table)?;
+    }
+    table.insert(SCHEMA_VERSION_KEY.to_owned(), Value::Integer(supported));
+    Ok(true)
+}
+
+/// Migrates the file at `path` in place. The original is kept as `<file>.v<version>.bak`.
+pub fn migrate_file(path: &Path, migrations: &[Migration]) -> Result<(), MigrationError> {
+    let contents = fs::read_to_string(path).map_err(MigrationError::Io)?;
+    let mut table: Table = toml::from_str(&contents).map_err(MigrationError::Parse)?;
+    let version = get_schema_version(&table);
+
+    if migrate(&mut table, migrations)? {
+        let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+        backup_name.push(format!(".v{version}.bak"));
+        fs::copy(path, path.with_file_name(backup_name)).map_err(MigrationError::Io)?;
+
+        let migrated = toml::to_string_pretty(&table)
+            .map_err(|error| MigrationError::Invalid(error.to_string()))?;
+        write_atomically(path, migrated.as_bytes()).map_err(MigrationError::Io)?;
+        // Otherwise the watcher would reload the file WAM is in the middle of loading.
+        watcher::record_own_write(path, migrated.as_bytes());
+    }
+    Ok(())
+}
+
+/// Returns the array of tables at `key`, treating a missing key as empty.
+pub fn get_table_array<'a>(
+    table: &'a mut Table,
+    key: &str,
+) -> Result<Vec<&'a mut Table>, MigrationError> {
+    match table.get_mut(key) {
+        Some(Value::Array(array)) => array
+            .iter_mut()
+            .map(|value| match value {
+                Value::Table(table) => Ok(table),
+                _ => Err(MigrationError::Invalid(format!(
+                    "'{key}' should only contain tables"
+                ))),
+            })
+            .collect(),
+        Some(_) => Err(MigrationError::Invalid(format!("'{key}' should be a list"))),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use confique::Config;
+    use tempfile::TempDir;
+    use toml::Table;
+
+    use crate::data::{
+        addons::{Addons, ReleaseChannel},
+        config::AppConfig,
+        state::PersistentState,
+        LocalSaveData,
+    };
+
+    use super::{get_schema_version, migrate, migrate_file, MigrationError};
+
+    /// Copies a fixture into its own temporary folder so tests can migrate it in place. The
+    /// folder is deleted when the returned `TempDir` is dropped.
+    fn copy_fixture(name: &str, contents: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    fn migrate_fixture<T: LocalSaveData + Config>(name: &str, contents: &str) -> (T, Table) {
+        let (_dir, path) = copy_fixture(name, contents);
+        migrate_file(&path, T::get_migrations()).unwrap_or_else(|error| panic!("{error}"));
+
+        let table: Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let loaded = T::builder().file(&path).load().unwrap();
+        (loaded, table)
+    }
+
+    #[test]
+    fn addons_v0() {
+        let (addons, table) =
+            migrate_fixture::<Addons>("addons.v0.toml", include_str!("fixtures/addons.v0.toml"));
+
+        assert_eq!(
+            get_schema_version(&table),
+            Addons::get_migrations().len() as i64
+        );
+        let retail_list = addons.get_list(crate::game::ReleaseType::Retail);
+        assert_eq!(retail_list.len(), 2);
+        assert_eq!(retail_list[0].name, "Details! Damage Meter");
+        assert!(retail_list[0].folders.is_empty());
+        assert_eq!(retail_list[0].channel, ReleaseChannel::Stable);
+        assert!(!retail_list[0].pinned);
+        assert_eq!(retail_list[1].version_name, None);
+    }
+
+    #[test]
+    fn addons_v1() {
+        let (addons, table) =
+            migrate_fixture::<Addons>("addons.v1.toml", include_str!("fixtures/addons.v1.toml"));
+
+        assert_eq!(
+            get_schema_version(&table),
+            Addons::get_migrations().len() as i64
+        );
+        let retail_list = addons.get_list(crate::game::ReleaseType::Retail);
+        assert_eq!(
+            retail_list[0].folders,
+            ["ElvUI", "ElvUI_Libraries", "ElvUI_Options"]
+        );
+        assert_eq!(retail_list[0].project_id.as_deref(), Some("1234"));
+        assert_eq!(retail_list[0].channel, ReleaseChannel::Stable);
+        assert!(!retail_list[0].pinned);
+        assert!(retail_list[0].settings_snapshots.is_empty());
+    }
+
+    #[test]
+    fn addons_v2() {
+        let (addons, table) =
+            migrate_fixture::<Addons>("addons.v2.toml", include_str!("fixtures/addons.v2.toml"));
+
+        assert_eq!(
+            get_schema_version(&table),
+            Addons::get_migrations().len() as i64
+        );
+        let retail_list = addons.get_list(crate::game::ReleaseType::Retail);
+        assert_eq!(retail_list.len(), 2);
+        assert_eq!(retail_list[0].channel, ReleaseChannel::Beta);
+        assert!(retail_list[0].pinned);
+        assert_eq!(retail_list[1].folders, ["Details"]);
+        assert!(retail_list
+            .iter()
+            .all(|addon| addon.settings_snapshots.is_empty()));
+    }
+
+    #[test]
+    fn config_v0() {
+        let (config, table) =
+            migrate_fixture::<AppConfig>("config.v0.toml", include_str!("fixtures/config.v0.toml"));
+
+        assert_eq!(
+            get_schema_version(&table),
+            AppConfig::get_migrations().len() as i64
+        );
+        assert_eq!(
+            config.get_game_path(crate::game::ReleaseType::Retail),
+            &Some(String::from("/games/World of Warcraft"))
+        );
+    }
+
+    #[test]
+    fn state_v0() {
+        let (_, table) = migrate_fixture::<PersistentState>(
+            "state.v0.toml",
+            include_str!("fixtures/state.v0.toml"),
+        );
+
+        assert_eq!(
+            get_schema_version(&table),
+            PersistentState::get_migrations().len() as i64
+        );
+    }
+
+    #[test]
+    fn backup_keeps_the_original() {
+        let contents = include_str!("fixtures/addons.v0.toml");
+        let (_dir, path) = copy_fixture("addons.backup.toml", contents);
+        migrate_file(&path, Addons::get_migrations()).unwrap_or_else(|error| panic!("{error}"));
+
+        let backup = path.with_file_name("addons.backup.toml.v0.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), contents);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let mut table: Table = toml::from_str("schema_version = 999").unwrap();
+        assert!(matches!(
+            migrate(&mut table, Addons::get_migrations()),
+            Err(MigrationError::NewerSchema { found: 999, .. })
+        ));
+    }
+
+    #[test]
+    fn current_schema_is_untouched() {
+        let mut table: Table = toml::from_str(&format!(
+            "schema_version = {}",
+            Addons::get_migrations().len()
+        ))
+        .unwrap();
+        assert!(matches!(
+            migrate(&mut table, Addons::get_migrations()),
+            Ok(false)
+        ));
+    }
+}
@@ -1,51 +1,456 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use confique::{Config, Error};
-use platform_dirs::AppDirs;
+use location::DataKind;
+use migrations::{Migration, MigrationError, SCHEMA_VERSION_KEY};
 use serde::Serialize;
 
 pub mod addons;
 pub mod config;
+pub mod location;
+pub mod migrations;
+pub mod profiles;
+pub mod queue;
+pub mod state;
+pub mod watcher;
+
+/// Files that couldn't be loaded but were left in place, so that saving the defaults WAM runs
+/// with instead doesn't overwrite them. A file leaves the set once it loads.
+/// How many earlier versions `write_atomically` keeps of a file.
+const BACKUP_COUNT: u32 = 3;
+
+static UNLOADED_FILES: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+pub enum LoadError {
+    /// The platform's data directories could not be resolved.
+    NoDataDir,
+    Migration(MigrationError),
+    Invalid(Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NoDataDir => write!(f, "the data directory could not be found"),
+            LoadError::Migration(error) => write!(f, "{error}"),
+            LoadError::Invalid(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl LoadError {
+    /// Whether the file itself is broken, rather than written by a newer WAM or unreadable for
+    /// now. Only a broken file is moved aside, since nothing can load it as it is.
+    fn is_broken_file(&self) -> bool {
+        matches!(
+            self,
+            LoadError::Invalid(_)
+                | LoadError::Migration(MigrationError::Parse(_) | MigrationError::Invalid(_))
+        )
+    }
+}
+
+pub enum SaveError {
+    NoDataDir,
+    /// The file couldn't be loaded and was left as it was, so it isn't overwritten.
+    Unloaded,
+    Serialize(toml::ser::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::NoDataDir => write!(f, "the data directory could not be found"),
+            SaveError::Unloaded => write!(
+                f,
+                "the file could not be loaded, so it is left as it is until it can be"
+            ),
+            SaveError::Serialize(error) => write!(f, "{error}"),
+            SaveError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// A problem WAM recovered from while loading its files, shown to the user after launch.
+#[derive(Clone, Serialize)]
+pub struct StartupDiagnostic {
+    pub file: String,
+    pub message: String,
+    /// Where the unreadable file was moved to, if it was.
+    pub backup_path: Option<String>,
+}
 
 pub trait LocalSaveData {
     type Data: Config + LocalSaveData + Serialize;
 
-    fn load() -> Result<Self::Data, Error> {
-        Self::Data::builder()
-            .file(get_or_create_data_dir::<Self::Data>(&Self::Data::get_file_name()))
+    fn load() -> Result<Self::Data, LoadError> {
+        let path = get_or_create_data_dir::<Self::Data>(&Self::Data::get_file_name())
+            .ok_or(LoadError::NoDataDir)?;
+        migrations::migrate_file(&path, Self::Data::get_migrations())
+            .map_err(LoadError::Migration)?;
+
+        let data = Self::Data::builder()
+            .file(&path)
             .load()
+            .map_err(LoadError::Invalid)?;
+        set_unloaded(&path, false);
+        Ok(data)
     }
 
-    fn save(&self) where Self: Serialize {
-        match toml::to_string_pretty(self) {
-            Ok(string) => {
-                fs::write(get_or_create_data_dir::<Self::Data>(&Self::Data::get_file_name()), string)
-                    .expect(&format!("failed to write '{}' to file", Self::Data::get_file_name()));
+    /// Loads the file, falling back to defaults when it can't be read. A broken file is moved
+    /// aside so the user can recover it. Any other file is left as it is and isn't saved over,
+    /// so a newer WAM's data survives. What happened is added to `diagnostics`.
+    ///
+    /// This runs once at startup, so it also moves the file out of the old single folder layout.
+    fn load_or_recover(diagnostics: &mut Vec<StartupDiagnostic>) -> Self::Data {
+        let file_name = Self::Data::get_file_name();
+        if let Some(location) = location::get() {
+            if let Err(error) =
+                location.migrate_legacy_file(&file_name, Self::Data::get_data_kind())
+            {
+                diagnostics.push(StartupDiagnostic {
+                    file: file_name.clone(),
+                    message: format!("the file could not be moved to its new folder: {error}"),
+                    backup_path: None,
+                });
+            }
+        }
+
+        match Self::load() {
+            Ok(data) => data,
+            Err(error) => {
+                let (message, backup_path) = match get_or_create_data_dir::<Self::Data>(&file_name)
+                {
+                    Some(path) => recover_file(&path, &error),
+                    None => (error.to_string(), None),
+                };
+                if backup_path.is_some() {
+                    let _ = Self::Data::new().save();
+                }
+
+                diagnostics.push(StartupDiagnostic {
+                    file: file_name,
+                    message,
+                    backup_path: backup_path
+                        .map(|backup_path| backup_path.to_string_lossy().into_owned()),
+                });
+                Self::Data::new()
             }
-            Err(error) => todo!("{error}"),
         }
     }
 
+    fn save(&self) -> Result<(), SaveError>
+    where
+        Self: Serialize,
+    {
+        let path = get_or_create_data_dir::<Self::Data>(&Self::Data::get_file_name())
+            .ok_or(SaveError::NoDataDir)?;
+        if is_unloaded(&path) {
+            return Err(SaveError::Unloaded);
+        }
+        let string = format!(
+            "{SCHEMA_VERSION_KEY} = {}\n\n{}",
+            Self::Data::get_migrations().len(),
+            toml::to_string_pretty(self).map_err(SaveError::Serialize)?
+        );
+        write_atomically(&path, string.as_bytes()).map_err(SaveError::Io)?;
+        watcher::record_own_write(&path, string.as_bytes());
+        Ok(())
+    }
+
     fn new() -> Self;
 
     fn get_file_name() -> String;
+
+    /// Which of WAM's folders the file belongs in.
+    fn get_data_kind() -> DataKind {
+        DataKind::Config
+    }
+
+    /// The steps that upgrade older versions of the file, oldest first. The file's current
+    /// `schema_version` is the number of steps.
+    fn get_migrations() -> &'static [Migration] {
+        &[]
+    }
 }
 
-fn get_or_create_data_dir<T: LocalSaveData + Config + Serialize>(file: &str) -> PathBuf {
-    let app_dirs = AppDirs::new(Some("wam1"), true);
-    match app_dirs {
-        Some(app_dirs) => {
-            let config_path = app_dirs.config_dir.join(file);
+/// Appends the diagnostics to `startup.log` in the log folder, so they outlive the session.
+pub fn log_startup_diagnostics(diagnostics: &[StartupDiagnostic]) {
+    if diagnostics.is_empty() {
+        return;
+    }
+    let Some(log_dir) = location::get_or_create_dir(DataKind::Logs) else {
+        return;
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
 
-            if !config_path.exists() {
-                fs::create_dir_all(&app_dirs.config_dir)
-                    .expect("Failed to create config directory");
-                fs::write(&config_path, "# nothing yet")
-                    .expect("Failed to create initial config file");
-                T::new().save();
+    if let Ok(mut log) = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_dir.join("startup.log"))
+    {
+        for diagnostic in diagnostics {
+            let _ = writeln!(
+                log,
+                "[{timestamp}] {}: {} (backup: {})",
+                diagnostic.file,
+                diagnostic.message,
+                diagnostic.backup_path.as_deref().unwrap_or("none")
+            );
+        }
+    }
+}
+
+/// Replaces `path` with `contents` so that a crash leaves either the old or the new file,
+/// never a truncated one. The previous contents are kept next to it as `<file>.bak.1`, and
+/// older ones are shifted up to `<file>.bak.3`.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    replace_through_temp_file(path, contents, true)
+}
+
+/// Like `write_atomically`, without the `.bak` copies. The game keeps its own `.bak` files in
+/// `WTF`, so WAM mustn't add or overwrite any there.
+pub fn replace_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    replace_through_temp_file(path, contents, false)
+}
+
+fn replace_through_temp_file(path: &Path, contents: &[u8], keep_backup: bool) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_os_string();
+    let with_suffix = |suffix: &str| {
+        let mut name = file_name.clone();
+        name.push(suffix);
+        path.with_file_name(name)
+    };
+
+    let temp_path = with_suffix(".tmp");
+    let result = (|| {
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(contents)?;
+        temp_file.sync_all()?;
+        drop(temp_file);
+
+        if keep_backup && path.exists() {
+            for number in (1..BACKUP_COUNT).rev() {
+                let backup = with_suffix(&format!(".bak.{number}"));
+                if backup.exists() {
+                    fs::rename(backup, with_suffix(&format!(".bak.{}", number + 1)))?;
+                }
             }
-            config_path
+            fs::copy(path, with_suffix(".bak.1"))?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error);
+    }
+
+    // The rename itself is only durable once the directory entry is flushed. The file is
+    // already replaced by now, so a failure here isn't reported as a failed write.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Moves a broken file aside, or marks any other file that failed to load so it isn't saved
+/// over. Returns the message to show and where the file was moved to.
+fn recover_file(path: &Path, error: &LoadError) -> (String, Option<PathBuf>) {
+    let message = error.to_string();
+    if !error.is_broken_file() {
+        set_unloaded(path, true);
+        return (
+            format!("{message} (the file was left as it is and won't be saved over)"),
+            None,
+        );
+    }
+
+    let backup = get_backup_path(path);
+    match fs::rename(path, &backup) {
+        Ok(()) => (message, Some(backup)),
+        // The broken file is the only copy, so the defaults stay in memory.
+        Err(rename_error) => {
+            set_unloaded(path, true);
+            (
+                format!(
+                    "{message} (the file could not be backed up and was left as it is: \
+                     {rename_error})"
+                ),
+                None,
+            )
+        }
+    }
+}
+
+fn set_unloaded(path: &Path, unloaded: bool) {
+    if let Ok(mut unloaded_files) = UNLOADED_FILES.get_or_init(Default::default).lock() {
+        if unloaded {
+            unloaded_files.insert(path.to_path_buf());
+        } else {
+            unloaded_files.remove(path);
+        }
+    }
+}
+
+fn is_unloaded(path: &Path) -> bool {
+    match UNLOADED_FILES.get_or_init(Default::default).lock() {
+        Ok(unloaded_files) => unloaded_files.contains(path),
+        Err(_) => false,
+    }
+}
+
+/// `addons.toml` becomes `addons.toml.broken-<unix time>`.
+fn get_backup_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".broken-{timestamp}"));
+    path.with_file_name(file_name)
+}
+
+fn get_or_create_data_dir<T: LocalSaveData + Config + Serialize>(file: &str) -> Option<PathBuf> {
+    let location = location::get()?;
+    let data_dir = location.get_dir(T::get_data_kind());
+    let config_path = data_dir.join(file);
+
+    if !config_path.exists() {
+        fs::create_dir_all(data_dir).ok()?;
+        fs::write(&config_path, "# nothing yet").ok()?;
+        T::new().save().ok()?;
+    }
+    Some(config_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+
+    use super::{
+        is_unloaded,
+        migrations::{self, MigrationError},
+        recover_file, replace_atomically, write_atomically, LoadError,
+    };
+
+    #[test]
+    fn replacing_leaves_no_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ElvUI.lua");
+        fs::write(&path, "old").unwrap();
+        fs::write(dir.path().join("ElvUI.lua.bak"), "the game's").unwrap();
+
+        replace_atomically(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("ElvUI.lua.bak")).unwrap(),
+            "the game's"
+        );
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["ElvUI.lua", "ElvUI.lua.bak"]);
+    }
+
+    #[test]
+    fn writing_keeps_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addons.toml");
+        fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("addons.toml.bak.1")).unwrap(),
+            "old"
+        );
+        assert!(!dir.path().join("addons.toml.tmp").exists());
+    }
+
+    #[test]
+    fn writing_rotates_the_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addons.toml");
+        fs::write(&path, "1").unwrap();
+
+        for contents in ["2", "3", "4", "5"] {
+            write_atomically(&path, contents.as_bytes()).unwrap();
         }
-        None => todo!(),
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "5");
+        let backup = |number: u32| {
+            fs::read_to_string(dir.path().join(format!("addons.toml.bak.{number}"))).unwrap()
+        };
+        assert_eq!([backup(1), backup(2), backup(3)], ["4", "3", "2"]);
+        assert!(!dir.path().join("addons.toml.bak.4").exists());
+    }
+
+    #[test]
+    fn broken_files_are_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addons.toml");
+        fs::write(&path, "[[retail").unwrap();
+        let error = migrations::migrate_file(&path, &[]).unwrap_err();
+
+        let (_, backup_path) = recover_file(&path, &LoadError::Migration(error));
+
+        let backup_path = backup_path.unwrap();
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(backup_path).unwrap(), "[[retail");
+        assert!(!is_unloaded(&path));
+    }
+
+    #[test]
+    fn newer_files_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addons.toml");
+        fs::write(&path, "schema_version = 99\n").unwrap();
+        let error = migrations::migrate_file(&path, &[]).unwrap_err();
+        assert!(matches!(
+            error,
+            MigrationError::NewerSchema { found: 99, .. }
+        ));
+
+        let (message, backup_path) = recover_file(&path, &LoadError::Migration(error));
+
+        assert!(backup_path.is_none());
+        assert!(message.contains("newer"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "schema_version = 99\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(is_unloaded(&path));
+    }
+
+    #[test]
+    fn unreadable_files_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "").unwrap();
+        let error = io::Error::from(io::ErrorKind::PermissionDenied);
+
+        let (_, backup_path) =
+            recover_file(&path, &LoadError::Migration(MigrationError::Io(error)));
+
+        assert!(backup_path.is_none());
+        assert!(path.exists());
+        assert!(is_unloaded(&path));
     }
 }
@@ -0,0 +1,59 @@
+use confique::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::game::ReleaseType;
+
+use super::{location::DataKind, LocalSaveData};
+
+/// Named sets of enabled addons, e.g. one for raiding and one for streaming.
+#[derive(Config, Serialize)]
+pub struct Profiles {
+    retail_list: Vec<Profile>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub name: String,
+    /// Names of managed addons, so that folders added by later updates are included.
+    pub managed: Vec<String>,
+    /// Folders inside `Interface/AddOns` that WAM doesn't manage.
+    pub unmanaged: Vec<String>,
+}
+
+impl Profiles {
+    pub fn get_list(&self, release_type: ReleaseType) -> &Vec<Profile> {
+        match release_type {
+            ReleaseType::Retail => &self.retail_list,
+        }
+    }
+
+    pub fn get_list_mut(&mut self, release_type: ReleaseType) -> &mut Vec<Profile> {
+        match release_type {
+            ReleaseType::Retail => &mut self.retail_list,
+        }
+    }
+
+    pub fn get(&self, release_type: ReleaseType, name: &str) -> Option<&Profile> {
+        self.get_list(release_type)
+            .iter()
+            .find(|profile| profile.name == name)
+    }
+}
+
+impl LocalSaveData for Profiles {
+    type Data = Profiles;
+
+    fn new() -> Self {
+        Profiles {
+            retail_list: Vec::new(),
+        }
+    }
+
+    fn get_file_name() -> String {
+        String::from("profiles.toml")
+    }
+
+    fn get_data_kind() -> DataKind {
+        DataKind::State
+    }
+}
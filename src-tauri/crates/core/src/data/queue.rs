@@ -0,0 +1,126 @@
+use confique::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::operations::Operation;
+
+use super::{location::DataKind, LocalSaveData};
+
+/// Operations waiting for the game to exit, kept on disk so they survive restarting WAM.
+#[derive(Config, Serialize)]
+pub struct OperationQueue {
+    operations: Vec<QueuedOperation>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct QueuedOperation {
+    pub operation: Operation,
+    /// Why the operation failed when it was run. It stays queued, but isn't run again until it
+    /// is retried.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl QueuedOperation {
+    pub fn new(operation: Operation) -> Self {
+        QueuedOperation {
+            operation,
+            error: None,
+        }
+    }
+}
+
+impl OperationQueue {
+    pub fn get_list(&self) -> &Vec<QueuedOperation> {
+        &self.operations
+    }
+
+    pub fn get_list_mut(&mut self) -> &mut Vec<QueuedOperation> {
+        &mut self.operations
+    }
+
+    /// Whether any operation is waiting to be run, not counting failed ones.
+    pub fn has_pending(&self) -> bool {
+        self.operations.iter().any(|queued| queued.error.is_none())
+    }
+}
+
+impl LocalSaveData for OperationQueue {
+    type Data = OperationQueue;
+
+    fn new() -> Self {
+        OperationQueue {
+            operations: Vec::new(),
+        }
+    }
+
+    fn get_file_name() -> String {
+        String::from("queue.toml")
+    }
+
+    fn get_data_kind() -> DataKind {
+        DataKind::State
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, env, fs};
+
+    use confique::Config;
+
+    use crate::{game::ReleaseType, operations::Operation, wtf::WtfTarget};
+
+    use super::{OperationQueue, QueuedOperation};
+
+    #[test]
+    fn queue_round_trip() {
+        let queue = OperationQueue {
+            operations: vec![
+                QueuedOperation::new(Operation::SwitchProfile {
+                    release_type: ReleaseType::Retail,
+                    name: String::from("Raiding"),
+                    target: WtfTarget {
+                        account: String::from("ACCOUNT"),
+                        realm: Some(String::from("Realm")),
+                        character: Some(String::from("Character")),
+                    },
+                }),
+                QueuedOperation {
+                    operation: Operation::SetCvars {
+                        release_type: ReleaseType::Retail,
+                        account: None,
+                        cvars: BTreeMap::from([(String::from("maxFPS"), String::from("60"))]),
+                    },
+                    error: Some(String::from("Config.wtf could not be written")),
+                },
+                QueuedOperation::new(Operation::SetAddonsEnabled {
+                    release_type: ReleaseType::Retail,
+                    target: None,
+                    folders: vec![String::from("ElvUI")],
+                    enabled: false,
+                }),
+            ],
+        };
+        let path = env::temp_dir().join(format!("wam-queue-{}.toml", std::process::id()));
+        fs::write(&path, toml::to_string_pretty(&queue).unwrap()).unwrap();
+
+        let loaded = OperationQueue::builder().file(&path).load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.operations.len(), 3);
+        assert!(matches!(
+            &loaded.operations[0].operation,
+            Operation::SwitchProfile { name, target, .. }
+                if name == "Raiding" && target.character.as_deref() == Some("Character")
+        ));
+        assert_eq!(
+            loaded.operations[1].error.as_deref(),
+            Some("Config.wtf could not be written")
+        );
+        assert!(matches!(
+            &loaded.operations[2].operation,
+            Operation::SetAddonsEnabled { folders, enabled: false, .. } if folders == &["ElvUI"]
+        ));
+        assert!(loaded.has_pending());
+    }
+}
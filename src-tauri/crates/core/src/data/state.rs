@@ -0,0 +1,68 @@
+use confique::Config;
+use serde::{Deserialize, Serialize};
+
+use crate::game::ReleaseType;
+
+use super::{location::DataKind, LocalSaveData};
+
+/// Things WAM remembers between launches that aren't user settings.
+#[derive(Config, Serialize)]
+pub struct PersistentState {
+    #[config(nested)]
+    builds: Builds,
+}
+
+#[derive(Config, Serialize)]
+struct Builds {
+    retail: Option<BuildRecord>,
+}
+
+/// The last client build WAM saw for a flavor, and the one before it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct BuildRecord {
+    pub current: String,
+    pub previous: Option<String>,
+}
+
+impl PersistentState {
+    /// Records `version` as the current build, returning the build it replaced if it changed.
+    pub fn update_build(&mut self, release_type: ReleaseType, version: &str) -> Option<String> {
+        let record = match release_type {
+            ReleaseType::Retail => &mut self.builds.retail,
+        };
+
+        match record {
+            Some(record) if record.current == version => None,
+            Some(record) => {
+                let previous = std::mem::replace(&mut record.current, version.to_owned());
+                record.previous = Some(previous.clone());
+                Some(previous)
+            }
+            None => {
+                *record = Some(BuildRecord {
+                    current: version.to_owned(),
+                    previous: None,
+                });
+                None
+            }
+        }
+    }
+}
+
+impl LocalSaveData for PersistentState {
+    type Data = PersistentState;
+
+    fn new() -> Self {
+        Self {
+            builds: Builds { retail: None },
+        }
+    }
+
+    fn get_file_name() -> String {
+        String::from("state.toml")
+    }
+
+    fn get_data_kind() -> DataKind {
+        DataKind::State
+    }
+}
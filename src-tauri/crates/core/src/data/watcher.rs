@@ -0,0 +1,177 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use confique::Config;
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::AppState;
+
+use super::{
+    addons::Addons,
+    config::AppConfig,
+    location::{self, DataKind},
+    LocalSaveData,
+};
+
+/// Editors tend to write a file in several steps, so events are collected for this long.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Hashes of what WAM itself last wrote to each file, so its own saves aren't reloaded.
+static OWN_WRITES: OnceLock<Mutex<HashMap<PathBuf, u64>>> = OnceLock::new();
+
+#[derive(Clone, Serialize)]
+pub struct ReloadEvent {
+    pub file: String,
+    /// Why the file was rejected. The previous in-memory data is kept when this is set.
+    pub error: Option<String>,
+}
+
+fn hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn record_own_write(path: &Path, contents: &[u8]) {
+    if let Ok(mut own_writes) = OWN_WRITES.get_or_init(Default::default).lock() {
+        own_writes.insert(path.to_path_buf(), hash(contents));
+    }
+}
+
+fn is_own_write(path: &Path, contents: &[u8]) -> bool {
+    match OWN_WRITES.get_or_init(Default::default).lock() {
+        Ok(own_writes) => own_writes.get(path) == Some(&hash(contents)),
+        Err(_) => false,
+    }
+}
+
+/// Watches the data folders and reloads `config.toml` and `addons.toml` when they are edited
+/// outside of WAM. The frontend is told through the `data-reloaded` event.
+pub fn watch_data_files(app_handle: AppHandle) {
+    let Some(location) = location::get() else {
+        return;
+    };
+    let watched = [
+        (
+            location
+                .get_dir(DataKind::Config)
+                .join(AppConfig::get_file_name()),
+            AppConfig::get_file_name(),
+        ),
+        (
+            location
+                .get_dir(DataKind::State)
+                .join(Addons::get_file_name()),
+            Addons::get_file_name(),
+        ),
+    ];
+
+    let (sender, receiver) = mpsc::channel();
+    let Ok(mut watcher) = notify::recommended_watcher(sender) else {
+        return;
+    };
+    for kind in [DataKind::Config, DataKind::State] {
+        let _ = watcher.watch(location.get_dir(kind), RecursiveMode::NonRecursive);
+    }
+
+    thread::spawn(move || {
+        // The watcher stops when it is dropped, so it lives as long as this thread.
+        let _watcher = watcher;
+
+        while let Ok(event) = receiver.recv() {
+            let mut paths: Vec<PathBuf> = event.map(|event| event.paths).unwrap_or_default();
+            while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+                paths.extend(event.map(|event| event.paths).unwrap_or_default());
+            }
+
+            for (path, file) in &watched {
+                // Compared by name, since the watcher may report canonicalized paths.
+                if !paths
+                    .iter()
+                    .any(|changed| changed.file_name() == path.file_name())
+                {
+                    continue;
+                }
+                // A deleted file would otherwise be recreated with defaults.
+                let Ok(contents) = fs::read(path) else {
+                    continue;
+                };
+                if is_own_write(path, &contents) {
+                    continue;
+                }
+
+                let state = app_handle.state::<AppState>();
+                let result = if *file == AppConfig::get_file_name() {
+                    reload(&state.config)
+                } else {
+                    reload(&state.addons)
+                };
+                record_own_write(path, &contents);
+
+                let _ = app_handle.emit_all(
+                    "data-reloaded",
+                    ReloadEvent {
+                        file: file.clone(),
+                        error: result.err(),
+                    },
+                );
+            }
+        }
+    });
+}
+
+fn reload<T: Config + LocalSaveData<Data = T>>(data: &Mutex<T>) -> Result<(), String> {
+    let loaded = T::load().map_err(|error| error.to_string())?;
+    match data.lock() {
+        Ok(mut data) => {
+            *data = loaded;
+            Ok(())
+        }
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::data::{addons::Addons, migrations, LocalSaveData};
+
+    use super::{is_own_write, record_own_write};
+
+    #[test]
+    fn own_writes_are_recognized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        record_own_write(&path, b"game_path = \"/games\"");
+
+        assert!(is_own_write(&path, b"game_path = \"/games\""));
+        assert!(!is_own_write(&path, b"game_path = \"/elsewhere\""));
+        assert!(!is_own_write(
+            &dir.path().join("addons.toml"),
+            b"game_path = \"/games\""
+        ));
+    }
+
+    #[test]
+    fn migrated_files_are_own_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addons.toml");
+        fs::write(&path, include_str!("fixtures/addons.v1.toml")).unwrap();
+
+        migrations::migrate_file(&path, Addons::get_migrations())
+            .unwrap_or_else(|error| panic!("{error}"));
+
+        assert!(is_own_write(&path, &fs::read(&path).unwrap()));
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    data::addons::{AddonData, AddonProvider},
+    game::{self, ReleaseType},
+    inventory::{self, InstalledAddon},
+    toc::Toc,
+    AppState,
+};
+
+#[derive(Serialize)]
+pub struct DependencyReport {
+    pub missing: Vec<MissingDependency>,
+    /// Each cycle is listed once, starting from its alphabetically first folder.
+    pub circular: Vec<Vec<String>>,
+    pub disabled: Vec<DisabledDependency>,
+}
+
+#[derive(Serialize)]
+pub struct MissingDependency {
+    pub name: String,
+    pub required_by: Vec<String>,
+    /// Places the dependency could be installed from, most specific first.
+    pub candidates: Vec<ResolutionCandidate>,
+}
+
+#[derive(Serialize)]
+pub struct DisabledDependency {
+    pub name: String,
+    pub required_by: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+pub struct ResolutionCandidate {
+    pub provider: AddonProvider,
+    /// When this is `None` the provider has to be searched for the dependency's name.
+    pub project_id: Option<String>,
+}
+
+/// Builds the dependency graph of the installed addons that `is_enabled` reports as loaded.
+pub fn resolve(
+    addons_path: &Path,
+    installed: &[InstalledAddon],
+    managed: &[AddonData],
+    is_enabled: impl Fn(&InstalledAddon) -> bool,
+) -> DependencyReport {
+    let by_name: HashMap<String, &InstalledAddon> = installed
+        .iter()
+        .map(|addon| (addon.folder.to_ascii_lowercase(), addon))
+        .collect();
+
+    let mut missing: BTreeMap<String, MissingDependency> = BTreeMap::new();
+    let mut disabled: BTreeMap<String, DisabledDependency> = BTreeMap::new();
+    let mut graph: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for addon in installed.iter().filter(|addon| is_enabled(addon)) {
+        let edges = graph.entry(addon.folder.clone()).or_default();
+
+        for dependency in addon.toc.get_required_dependencies() {
+            // Blizzard's own addons ship inside the client's archives.
+            if dependency.starts_with("Blizzard_") {
+                continue;
+            }
+
+            match by_name.get(&dependency.to_ascii_lowercase()) {
+                Some(installed_dependency) => {
+                    edges.push(installed_dependency.folder.clone());
+                    if !is_enabled(installed_dependency) {
+                        disabled
+                            .entry(installed_dependency.folder.clone())
+                            .or_insert_with(|| DisabledDependency {
+                                name: installed_dependency.folder.clone(),
+                                required_by: Vec::new(),
+                            })
+                            .required_by
+                            .push(addon.folder.clone());
+                    }
+                }
+                None => missing
+                    .entry(dependency.to_ascii_lowercase())
+                    .or_insert_with(|| MissingDependency {
+                        name: dependency.clone(),
+                        required_by: Vec::new(),
+                        candidates: Vec::new(),
+                    })
+                    .required_by
+                    .push(addon.folder.clone()),
+            }
+        }
+    }
+
+    let mut missing: Vec<MissingDependency> = missing.into_values().collect();
+    for dependency in &mut missing {
+        dependency.candidates = find_candidates(
+            addons_path,
+            &dependency.name,
+            &dependency.required_by,
+            managed,
+        );
+    }
+
+    DependencyReport {
+        missing,
+        circular: find_cycles(&graph),
+        disabled: disabled.into_values().collect(),
+    }
+}
+
+fn find_candidates(
+    addons_path: &Path,
+    name: &str,
+    required_by: &[String],
+    managed: &[AddonData],
+) -> Vec<ResolutionCandidate> {
+    let mut candidates = Vec::new();
+    let mut push = |candidate: ResolutionCandidate| {
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    };
+
+    // An entry that used to install this folder knows exactly where it came from.
+    for addon in managed.iter().filter(|addon| addon.claims_folder(name)) {
+        push(ResolutionCandidate {
+            provider: addon.provider,
+            project_id: addon.project_id.clone(),
+        });
+    }
+
+    // A folder without a TOC for this flavor still carries the author's `X-*` ids.
+    if let Some(toc) = Toc::read_any(&addons_path.join(name)) {
+        for (provider, id) in toc.get_provider_ids() {
+            push(ResolutionCandidate {
+                provider,
+                project_id: Some(id),
+            });
+        }
+    }
+
+    // Otherwise the provider the dependents came from is the best place to look.
+    for addon in managed
+        .iter()
+        .filter(|addon| required_by.iter().any(|folder| addon.claims_folder(folder)))
+    {
+        push(ResolutionCandidate {
+            provider: addon.provider,
+            project_id: None,
+        });
+    }
+
+    candidates
+}
+
+fn find_cycles(graph: &BTreeMap<String, Vec<String>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        node: &'a str,
+        graph: &'a BTreeMap<String, Vec<String>>,
+        stack: &mut Vec<&'a str>,
+        finished: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if finished.contains(&node) {
+            return;
+        }
+        if let Some(position) = stack.iter().position(|visiting| *visiting == node) {
+            let mut cycle: Vec<String> = stack[position..].iter().map(|s| s.to_string()).collect();
+            let first = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+            cycle.rotate_left(first);
+            if !cycles.contains(&cycle) {
+                cycles.push(cycle);
+            }
+            return;
+        }
+
+        stack.push(node);
+        for edge in graph.get(node).into_iter().flatten() {
+            visit(edge, graph, stack, finished, cycles);
+        }
+        stack.pop();
+        finished.insert(node);
+    }
+
+    let mut cycles = Vec::new();
+    let mut finished = HashSet::new();
+    for node in graph.keys() {
+        visit(node, graph, &mut Vec::new(), &mut finished, &mut cycles);
+    }
+    cycles
+}
+
+#[tauri::command]
+pub fn get_dependency_report(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Option<DependencyReport> {
+    let addons_path = game::get_addons_path(&*state.config.lock().ok()?, release_type)?;
+    let installed = inventory::scan(&addons_path, release_type);
+    let addons = state.addons.lock().ok()?;
+
+    Some(resolve(
+        &addons_path,
+        &installed,
+        addons.get_list(release_type),
+        |addon| !addon.toc.is_disabled_by_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{inventory::InstalledAddon, toc::Toc};
+
+    use super::{resolve, DependencyReport};
+
+    fn addon(folder: &str, toc: &str) -> InstalledAddon {
+        InstalledAddon {
+            folder: folder.to_owned(),
+            toc: Toc::parse(folder, toc),
+        }
+    }
+
+    fn resolve_all(installed: &[InstalledAddon]) -> DependencyReport {
+        resolve(
+            Path::new("/nonexistent/Interface/AddOns"),
+            installed,
+            &[],
+            |addon| !addon.toc.is_disabled_by_default(),
+        )
+    }
+
+    #[test]
+    fn chain() {
+        let report = resolve_all(&[
+            addon("Plugin", "## Dependencies: Core"),
+            addon("Core", "## RequiredDeps: Libs"),
+            addon("Libs", "## Title: Libs"),
+        ]);
+
+        assert!(report.missing.is_empty());
+        assert!(report.circular.is_empty());
+        assert!(report.disabled.is_empty());
+    }
+
+    #[test]
+    fn diamond() {
+        let report = resolve_all(&[
+            addon("Top", "## Dependencies: Left, Right"),
+            addon("Left", "## Dependencies: Bottom"),
+            addon("Right", "## Dependencies: Bottom"),
+            addon("Bottom", "## Title: Bottom"),
+        ]);
+
+        assert!(report.missing.is_empty());
+        assert!(report.circular.is_empty());
+    }
+
+    #[test]
+    fn cycle() {
+        let report = resolve_all(&[
+            addon("C", "## Dependencies: A"),
+            addon("A", "## Dependencies: B"),
+            addon("B", "## Dependencies: C"),
+            addon("Other", "## Dependencies: A"),
+        ]);
+
+        assert_eq!(report.circular, [["A", "B", "C"]]);
+    }
+
+    #[test]
+    fn missing_required_dependency() {
+        let report = resolve_all(&[
+            addon("PluginA", "## Dependencies: Core, Blizzard_Collections"),
+            addon("PluginB", "## RequiredDeps: core"),
+        ]);
+
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].name, "Core");
+        assert_eq!(report.missing[0].required_by, ["PluginA", "PluginB"]);
+    }
+
+    #[test]
+    fn optional_dependency() {
+        let report = resolve_all(&[addon("Plugin", "## OptionalDeps: Core")]);
+
+        assert!(report.missing.is_empty());
+        assert!(report.circular.is_empty());
+    }
+
+    #[test]
+    fn disabled_dependency() {
+        let report = resolve_all(&[
+            addon("Plugin", "## Dependencies: Core"),
+            addon("Core", "## DefaultState: disabled"),
+        ]);
+
+        assert_eq!(report.disabled.len(), 1);
+        assert_eq!(report.disabled[0].name, "Core");
+        assert_eq!(report.disabled[0].required_by, ["Plugin"]);
+    }
+}
@@ -1,39 +1,232 @@
 use std::{fs, path::PathBuf};
 
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tauri::State;
 
-use crate::AppState;
+use crate::{data::config::AppConfig, AppState};
 
-#[derive(Deserialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub enum ReleaseType {
     Retail,
 }
 
+impl ReleaseType {
+    pub const ALL: [ReleaseType; 1] = [ReleaseType::Retail];
+
+    /// The folder inside the game directory that holds this flavor's files.
+    pub fn get_folder_name(&self) -> &'static str {
+        match self {
+            ReleaseType::Retail => "_retail_",
+        }
+    }
+
+    /// Flavor specific TOC suffixes, checked in order before the plain `<Folder>.toc`.
+    pub fn get_toc_suffixes(&self) -> &'static [&'static str] {
+        match self {
+            ReleaseType::Retail => &["_Mainline", "-Mainline"],
+        }
+    }
+}
+
+/// Returns the flavor folder (e.g. `World of Warcraft/_retail_`) if a game path is configured.
+pub fn get_flavor_path(config: &AppConfig, release_type: ReleaseType) -> Option<PathBuf> {
+    config
+        .get_game_path(release_type)
+        .as_ref()
+        .map(|game_path| PathBuf::from(game_path).join(release_type.get_folder_name()))
+}
+
+/// Returns the flavor's `Interface/AddOns` folder if a game path is configured.
+pub fn get_addons_path(config: &AppConfig, release_type: ReleaseType) -> Option<PathBuf> {
+    get_flavor_path(config, release_type).map(|path| path.join("Interface").join("AddOns"))
+}
+
+/// Like `get_addons_path`, for commands that only have the app state.
+pub fn get_configured_addons_path(
+    state: &AppState,
+    release_type: ReleaseType,
+) -> Result<PathBuf, String> {
+    match state.config.lock() {
+        Ok(config) => get_addons_path(&config, release_type),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no game path is set"))
+}
+
+/// Reads the client version (e.g. `11.0.2.56647`) from the game's `.build.info`.
+pub fn read_game_version(config: &AppConfig, release_type: ReleaseType) -> Option<String> {
+    let game_path = config.get_game_path(release_type).as_ref()?;
+    let string = fs::read_to_string(PathBuf::from(game_path).join(".build.info")).ok()?;
+    let regex =
+        Regex::new(r"text\?\|\|\|(?<version>.*?)\|\|").expect("An invalid pattern was supplied");
+    regex
+        .captures(&string)
+        .map(|captures| captures["version"].to_owned())
+}
+
+/// Converts a client version into the number addons list in `## Interface`, so `11.0.2`
+/// becomes `110002`.
+pub fn get_interface_version(version: &str) -> Option<u32> {
+    let mut parts = version.split('.').map(|part| part.trim().parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some(major * 10000 + minor * 100 + patch)
+}
+
+/// The executables of every game client.
+pub const GAME_EXECUTABLES: [&str; 3] = ["Wow.exe", "WowClassic.exe", "WowB.exe"];
+
+/// A running game client.
+#[derive(Clone, Serialize)]
+pub struct GameProcess {
+    pub pid: u32,
+    /// One of [`GAME_EXECUTABLES`].
+    pub executable: String,
+}
+
+#[derive(Serialize)]
+pub struct GameState {
+    pub running: bool,
+    pub processes: Vec<GameProcess>,
+}
+
+/// Matches a process name or command line argument against the game executables. Under Wine
+/// the argument is a Windows path, e.g. `C:\World of Warcraft\_retail_\Wow.exe`.
+fn match_executable(name: &str) -> Option<&'static str> {
+    let file_name = name.trim().rsplit(['/', '\\']).next()?;
+    GAME_EXECUTABLES
+        .into_iter()
+        .find(|executable| file_name.eq_ignore_ascii_case(executable))
+}
+
+/// The programs Wine starts Windows executables with.
+#[cfg(target_os = "linux")]
+const WINE_LOADERS: [&str; 4] = ["wine", "wine64", "wine-preloader", "wine64-preloader"];
+
+/// Whether `name`, a process name or path, is one of [`WINE_LOADERS`].
+#[cfg(target_os = "linux")]
+fn is_wine_loader(name: &str) -> bool {
+    let file_name = name.trim().rsplit('/').next().unwrap_or_default();
+    WINE_LOADERS.contains(&file_name)
+}
+
+/// Matches a process by its `comm` name and its NUL separated `cmdline`. Wine usually names
+/// the process after the executable, but it can also show up as `wine64-preloader` with the
+/// executable as its first argument. That argument is only checked for Wine, since any other
+/// program (e.g. an editor) may have been given a path to the executable too.
+#[cfg(target_os = "linux")]
+fn match_process(comm: &str, cmdline: &str) -> Option<&'static str> {
+    let mut arguments = cmdline.split('\0');
+    let program = arguments.next().unwrap_or_default();
+    match_executable(comm)
+        .or_else(|| match_executable(program))
+        .or_else(|| {
+            if is_wine_loader(comm) || is_wine_loader(program) {
+                arguments.next().and_then(match_executable)
+            } else {
+                None
+            }
+        })
+}
+
+/// Finds running game clients through `/proc`.
+#[cfg(target_os = "linux")]
+pub fn find_game_processes() -> Vec<GameProcess> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let path = entry.path();
+            let comm = fs::read_to_string(path.join("comm")).unwrap_or_default();
+            let cmdline = fs::read(path.join("cmdline")).unwrap_or_default();
+            let executable = match_process(&comm, &String::from_utf8_lossy(&cmdline))?;
+            Some(GameProcess {
+                pid,
+                executable: executable.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn find_game_processes() -> Vec<GameProcess> {
+    Vec::new()
+}
+
+/// Whether a game client is running.
+pub fn is_game_running() -> bool {
+    !find_game_processes().is_empty()
+}
+
+#[tauri::command]
+pub fn get_game_state() -> GameState {
+    let processes = find_game_processes();
+    GameState {
+        running: !processes.is_empty(),
+        processes,
+    }
+}
+
 #[tauri::command]
 pub fn get_game_version(state: State<AppState>) -> Option<String> {
-    let config = state.config.lock();
-    match config {
-        Ok(config) => {
-            let game_path = config.get_game_path(ReleaseType::Retail);
-            if let Some(game_path) = game_path {
-                let mut build_info_path = PathBuf::from(game_path);
-                build_info_path.push(".build.info");
-                match fs::read_to_string(build_info_path) {
-                    Ok(string) => {
-                        let regex = Regex::new(r"text\?\|\|\|(?<version>.*?)\|\|")
-                            .expect("An invalid pattern was supplied");
-                        match regex.captures(&string) {
-                            Some(captures) => return Some(captures["version"].to_owned()),
-                            None => (),
-                        }
-                    }
-                    Err(_) => (),
-                }
-            }
+    if let Ok(config) = state.config.lock() {
+        if let Some(version) = read_game_version(&config, ReleaseType::Retail) {
+            return Some(version);
         }
-        Err(_) => (),
     }
     Some("Internal Error".to_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_os = "linux")]
+    use super::match_process;
+
+    use super::get_interface_version;
+
+    #[test]
+    fn interface_versions() {
+        assert_eq!(get_interface_version("11.0.2"), Some(110002));
+        assert_eq!(get_interface_version("11.0.2.56421"), Some(110002));
+        assert_eq!(get_interface_version("1.15.4"), Some(11504));
+        assert_eq!(get_interface_version("10.2"), Some(100200));
+        assert_eq!(get_interface_version("11"), Some(110000));
+        assert_eq!(get_interface_version(" 11 . 0 . 5 "), Some(110005));
+        assert_eq!(get_interface_version("11.x.2"), None);
+        assert_eq!(get_interface_version(""), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn game_processes() {
+        assert_eq!(match_process("Wow.exe\n", ""), Some("Wow.exe"));
+        assert_eq!(
+            match_process("WowClassic.exe", "C:\\Games\\_classic_\\WowClassic.exe\0"),
+            Some("WowClassic.exe")
+        );
+        assert_eq!(
+            match_process(
+                "wine64-preloader\n",
+                "/usr/bin/wine64-preloader\0C:\\World of Warcraft\\_retail_\\Wow.exe\0-launcherlogin\0"
+            ),
+            Some("Wow.exe")
+        );
+        assert_eq!(
+            match_process("wine\n", "wine\0Z:\\home\\user\\Games\\WowB.exe\0"),
+            Some("WowB.exe")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn other_programs_with_the_executable_as_an_argument() {
+        assert_eq!(match_process("vim\n", "vim\0/games/wow/Wow.exe\0"), None);
+        assert_eq!(match_process("hexdump\n", "hexdump\0Wow.exe\0"), None);
+        assert_eq!(match_process("bash\n", "/bin/bash\0"), None);
+    }
+}
@@ -0,0 +1,641 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Serialize;
+use tauri::State;
+use zip::ZipArchive;
+
+use crate::{
+    conflicts::{self, ConflictResolution, FolderConflict},
+    data::{
+        addons::{AddonData, SettingsSnapshot},
+        location::{self, DataKind},
+        LocalSaveData,
+    },
+    game::{self, ReleaseType},
+    operations::{self, Guarded, Operation},
+    toc::Toc,
+    wtf::{self, snapshots},
+    AppState,
+};
+
+/// Created next to `Interface/AddOns` rather than inside it, where the game and other addon
+/// managers would take it for an addon. Being on the same file system, the extracted folders
+/// can still be renamed into place.
+const STAGING_FOLDER: &str = ".wam-staging";
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum InstallError {
+    NoGamePath,
+    Io(String),
+    InvalidArchive(String),
+    /// The archive did not contain any addon folders.
+    Empty,
+    Conflicts(Vec<FolderConflict>),
+    /// The settings of the addons being replaced could not be snapshotted.
+    Snapshot(String),
+    /// The game is running and installs are set to be blocked until it exits.
+    GameRunning(String),
+}
+
+impl fmt::Display for InstallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallError::NoGamePath => write!(f, "no game path is set"),
+            InstallError::Io(error) => write!(f, "{error}"),
+            InstallError::InvalidArchive(error) => write!(f, "invalid archive: {error}"),
+            InstallError::Empty => write!(f, "the archive does not contain any addons"),
+            InstallError::Conflicts(conflicts) => {
+                let folders: Vec<&str> = conflicts
+                    .iter()
+                    .map(|conflict| conflict.folder.as_str())
+                    .collect();
+                write!(f, "folders already in use: {}", folders.join(", "))
+            }
+            InstallError::Snapshot(error) => {
+                write!(f, "the addon's settings could not be backed up: {error}")
+            }
+            InstallError::GameRunning(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl From<io::Error> for InstallError {
+    fn from(error: io::Error) -> Self {
+        InstallError::Io(error.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for InstallError {
+    fn from(error: zip::result::ZipError) -> Self {
+        InstallError::InvalidArchive(error.to_string())
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstallReport {
+    pub installed_folders: Vec<String>,
+    /// Folders left alone because of [`ConflictResolution::KeepExisting`].
+    pub skipped_folders: Vec<String>,
+    /// Folders from the previous version that the new one no longer ships.
+    pub removed_folders: Vec<String>,
+    /// The snapshot of the replaced folders' SavedVariables, if they had any.
+    pub settings_snapshot: Option<String>,
+    /// A snapshot of the settings from an earlier install of the same version, which can be
+    /// restored when going back to it.
+    pub restorable_snapshot: Option<String>,
+}
+
+/// Installs the addon folders inside `archive_path` and records them on `addon` in `managed`.
+/// The SavedVariables of any folders it replaces are snapshotted first.
+pub fn install_archive(
+    addons_path: &Path,
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    archive_path: &Path,
+    mut addon: AddonData,
+    managed: &mut Vec<AddonData>,
+    resolution: ConflictResolution,
+) -> Result<InstallReport, InstallError> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let folders = get_archive_folders(&mut archive)?;
+    if folders.is_empty() {
+        return Err(InstallError::Empty);
+    }
+
+    let conflicts = conflicts::find_install_conflicts(addons_path, &addon, &folders, managed);
+    let mut skipped_folders = Vec::new();
+    if !conflicts.is_empty() {
+        match resolution {
+            ConflictResolution::Abort => return Err(InstallError::Conflicts(conflicts)),
+            ConflictResolution::Replace => {
+                for conflict in &conflicts {
+                    for other in managed.iter_mut() {
+                        if !other.is_same_addon(&addon) {
+                            other
+                                .folders
+                                .retain(|folder| !folder.eq_ignore_ascii_case(&conflict.folder));
+                        }
+                    }
+                }
+            }
+            ConflictResolution::KeepExisting => {
+                skipped_folders = conflicts
+                    .into_iter()
+                    .map(|conflict| conflict.folder)
+                    .collect();
+            }
+        }
+    }
+    let installed_folders: Vec<String> = folders
+        .into_iter()
+        .filter(|folder| !skipped_folders.contains(folder))
+        .collect();
+
+    let previous = managed.iter().find(|other| other.is_same_addon(&addon));
+    let mut replaced_folders = installed_folders.clone();
+    if let Some(previous) = previous {
+        replaced_folders.extend(previous.folders.iter().cloned());
+    }
+    let settings_snapshot = snapshot_replaced_folders(
+        addons_path,
+        wtf_path,
+        release_type,
+        &addon,
+        replaced_folders,
+    )?;
+    addon.settings_snapshots = previous
+        .map(|previous| previous.settings_snapshots.clone())
+        .unwrap_or_default();
+    if let Some(snapshot) = &settings_snapshot {
+        addon.add_settings_snapshot(SettingsSnapshot {
+            snapshot_id: snapshot.clone(),
+            version_id: previous.map(|previous| previous.version_id.clone()),
+        });
+    }
+    let restorable_snapshot = addon
+        .settings_snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| snapshot.version_id.as_ref() == Some(&addon.version_id))
+        .map(|snapshot| snapshot.snapshot_id.clone());
+
+    let staging_path = addons_path.with_file_name(STAGING_FOLDER);
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)?;
+    }
+    extract(&mut archive, &staging_path, &installed_folders)?;
+    for folder in &installed_folders {
+        let target = addons_path.join(folder);
+        if target.exists() {
+            fs::remove_dir_all(&target)?;
+        }
+        fs::rename(staging_path.join(folder), target)?;
+    }
+    fs::remove_dir_all(&staging_path)?;
+
+    let mut removed_folders = Vec::new();
+    if let Some(previous) = managed.iter().find(|other| other.is_same_addon(&addon)) {
+        for folder in &previous.folders {
+            let still_shipped = installed_folders
+                .iter()
+                .any(|installed| installed.eq_ignore_ascii_case(folder));
+            let claimed_elsewhere = managed
+                .iter()
+                .any(|other| !other.is_same_addon(&addon) && other.claims_folder(folder));
+            if !still_shipped && !claimed_elsewhere && addons_path.join(folder).exists() {
+                fs::remove_dir_all(addons_path.join(folder))?;
+                removed_folders.push(folder.clone());
+            }
+        }
+    }
+
+    addon.folders = installed_folders.clone();
+    match managed.iter_mut().find(|other| other.is_same_addon(&addon)) {
+        Some(existing) => *existing = addon,
+        None => managed.push(addon),
+    }
+
+    Ok(InstallReport {
+        installed_folders,
+        skipped_folders,
+        removed_folders,
+        settings_snapshot,
+        restorable_snapshot,
+    })
+}
+
+/// Snapshots the SavedVariables of the folders an install is about to replace, returning the
+/// snapshot's id. Folders whose TOC doesn't declare any SavedVariables are left out.
+fn snapshot_replaced_folders(
+    addons_path: &Path,
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    addon: &AddonData,
+    mut folders: Vec<String>,
+) -> Result<Option<String>, InstallError> {
+    folders.sort_by_key(|folder| folder.to_ascii_lowercase());
+    folders.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    folders.retain(|folder| {
+        Toc::read(&addons_path.join(folder), release_type)
+            .is_some_and(|toc| toc.has_saved_variables())
+    });
+    if folders.is_empty() {
+        return Ok(None);
+    }
+
+    let snapshot = snapshots::create_for_folders(
+        wtf_path,
+        release_type,
+        format!("Before installing {} {}", addon.name, addon.version_id),
+        folders,
+    )
+    .map_err(InstallError::Snapshot)?;
+    Ok(snapshot.map(|snapshot| snapshot.id))
+}
+
+/// Cached archives are named `<provider>-<project id or name>-<version id>.zip`.
+fn get_cache_prefix(addon: &AddonData) -> String {
+    format!(
+        "{:?}-{}-",
+        addon.provider,
+        addon.project_id.as_deref().unwrap_or(&addon.name)
+    )
+    .replace(['/', '\\'], "_")
+}
+
+/// Keeps a copy of the installed archive so the version can be reinstalled without the provider.
+fn cache_archive(archive_path: &Path, addon: &AddonData) {
+    if let Some(cache_dir) = location::get_or_create_dir(DataKind::Cache) {
+        let archives_dir = cache_dir.join("archives");
+        let file_name = format!(
+            "{}{}.zip",
+            get_cache_prefix(addon),
+            addon.version_id.replace(['/', '\\'], "_")
+        );
+        if fs::create_dir_all(&archives_dir).is_ok() {
+            let _ = fs::copy(archive_path, archives_dir.join(file_name));
+        }
+    }
+}
+
+/// Finds the most recently cached archive of `addon`, along with its version id.
+pub fn find_cached_archive(addon: &AddonData) -> Option<(PathBuf, String)> {
+    let archives_dir = location::get()?.get_dir(DataKind::Cache).join("archives");
+    let prefix = get_cache_prefix(addon);
+
+    fs::read_dir(archives_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let version_id = file_name.strip_prefix(&prefix)?.strip_suffix(".zip")?;
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()?;
+            Some((modified, entry.path(), version_id.to_owned()))
+        })
+        .max_by_key(|(modified, _, _)| *modified)
+        .map(|(_, path, version_id)| (path, version_id))
+}
+
+/// The top level folders of an archive, which are the addon folders it installs.
+fn get_archive_folders(archive: &mut ZipArchive<File>) -> Result<Vec<String>, InstallError> {
+    let mut folders = BTreeSet::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let mut components = path.components();
+        if let (Some(Component::Normal(first)), Some(_)) = (components.next(), components.next()) {
+            if let Some(first) = first.to_str() {
+                folders.insert(first.to_owned());
+            }
+        }
+    }
+    Ok(folders.into_iter().collect())
+}
+
+fn extract(
+    archive: &mut ZipArchive<File>,
+    destination: &Path,
+    folders: &[String],
+) -> Result<(), InstallError> {
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let Some(path) = file.enclosed_name().map(PathBuf::from) else {
+            continue;
+        };
+        let in_folders = match path.components().next() {
+            Some(Component::Normal(first)) => folders.iter().any(|folder| first == folder.as_str()),
+            _ => false,
+        };
+        if !in_folders {
+            continue;
+        }
+
+        let output_path = destination.join(path);
+        if file.is_dir() {
+            fs::create_dir_all(&output_path)?;
+        } else {
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut file, &mut File::create(&output_path)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Installs the archive at `archive_path` into the flavor's AddOns folder and saves `addon`.
+pub fn install(
+    state: &AppState,
+    release_type: ReleaseType,
+    archive_path: &Path,
+    addon: AddonData,
+    resolution: ConflictResolution,
+) -> Result<InstallReport, InstallError> {
+    let (addons_path, wtf_path) = match state.config.lock() {
+        Ok(config) => (
+            game::get_addons_path(&config, release_type),
+            wtf::get_wtf_path(&config, release_type),
+        ),
+        Err(_) => (None, None),
+    };
+    let (Some(addons_path), Some(wtf_path)) = (addons_path, wtf_path) else {
+        return Err(InstallError::NoGamePath);
+    };
+
+    let mut addons = state
+        .addons
+        .lock()
+        .map_err(|error| InstallError::Io(error.to_string()))?;
+    let report = install_archive(
+        &addons_path,
+        &wtf_path,
+        release_type,
+        archive_path,
+        addon.clone(),
+        addons.get_list_mut(release_type),
+        resolution,
+    )?;
+    cache_archive(archive_path, &addon);
+    addons
+        .save()
+        .map_err(|error| InstallError::Io(error.to_string()))?;
+    drop(addons);
+    if report.settings_snapshot.is_some() {
+        snapshots::apply_configured_retention(state).map_err(InstallError::Snapshot)?;
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn install_addon_archive(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    archive_path: String,
+    addon: AddonData,
+    resolution: Option<ConflictResolution>,
+) -> Result<Guarded<InstallReport>, InstallError> {
+    let queued = operations::queue_if_running(&state, || Operation::Install {
+        release_type,
+        archive_path: archive_path.clone(),
+        addon: addon.clone(),
+        resolution,
+    })
+    .map_err(InstallError::GameRunning)?;
+    if queued {
+        return Ok(Guarded::Queued);
+    }
+
+    install(
+        &state,
+        release_type,
+        Path::new(&archive_path),
+        addon,
+        resolution.unwrap_or_default(),
+    )
+    .map(Guarded::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        io::Write,
+        path::Path,
+    };
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use crate::{
+        conflicts::{ConflictKind, ConflictResolution},
+        data::addons::{AddonData, AddonProvider, ReleaseChannel},
+        game::ReleaseType,
+    };
+
+    use super::{install_archive, InstallError, InstallReport, STAGING_FOLDER};
+
+    fn addon(name: &str, version_id: &str, folders: &[&str]) -> AddonData {
+        AddonData {
+            name: name.to_owned(),
+            version_id: version_id.to_owned(),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: Some(format!("{name}-id")),
+            folders: folders.iter().map(|folder| folder.to_string()).collect(),
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    /// Writes an addon folder whose `source.txt` says who put it there.
+    fn write_folder(addons_path: &Path, folder: &str, source: &str) {
+        let path = addons_path.join(folder);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join(format!("{folder}.toc")), "## Interface: 110002\n").unwrap();
+        fs::write(path.join("source.txt"), source).unwrap();
+    }
+
+    fn read_source(addons_path: &Path, folder: &str) -> String {
+        fs::read_to_string(addons_path.join(folder).join("source.txt")).unwrap()
+    }
+
+    /// An archive with `folders`, each saying it came from `source`.
+    fn write_archive(path: &Path, folders: &[&str], source: &str) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for folder in folders {
+            zip.start_file(format!("{folder}/{folder}.toc"), FileOptions::default())
+                .unwrap();
+            zip.write_all(b"## Interface: 110002\n").unwrap();
+            zip.start_file(format!("{folder}/source.txt"), FileOptions::default())
+                .unwrap();
+            zip.write_all(source.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Installs `incoming` from an archive of `folders` over an AddOns folder where `Base`
+    /// owns `Base` and `Shared`.
+    fn install_over_base(
+        folders: &[&str],
+        resolution: ConflictResolution,
+    ) -> (
+        tempfile::TempDir,
+        Vec<AddonData>,
+        Result<InstallReport, InstallError>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let addons_path = dir.path().join("Interface").join("AddOns");
+        write_folder(&addons_path, "Base", "Base");
+        write_folder(&addons_path, "Shared", "Base");
+        let mut managed = vec![addon("Base", "1", &["Base", "Shared"])];
+        let archive_path = dir.path().join("Plugin.zip");
+        write_archive(&archive_path, folders, "Plugin");
+
+        let result = install_archive(
+            &addons_path,
+            &dir.path().join("WTF"),
+            ReleaseType::Retail,
+            &archive_path,
+            addon("Plugin", "1", &[]),
+            &mut managed,
+            resolution,
+        );
+        (dir, managed, result)
+    }
+
+    fn get_folders<'a>(managed: &'a [AddonData], name: &str) -> &'a [String] {
+        &managed
+            .iter()
+            .find(|addon| addon.name == name)
+            .unwrap()
+            .folders
+    }
+
+    #[test]
+    fn abort_changes_nothing() {
+        let (dir, managed, result) =
+            install_over_base(&["Plugin", "Shared"], ConflictResolution::Abort);
+        let addons_path = dir.path().join("Interface").join("AddOns");
+
+        let Err(InstallError::Conflicts(conflicts)) = result else {
+            panic!("the install wasn't refused");
+        };
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].folder, "Shared");
+        assert!(matches!(
+            &conflicts[0].kind,
+            ConflictKind::SharedFolder { claimed_by } if claimed_by == &["Base"]
+        ));
+        assert_eq!(read_source(&addons_path, "Shared"), "Base");
+        assert!(!addons_path.join("Plugin").exists());
+        assert_eq!(managed.len(), 1);
+        assert_eq!(get_folders(&managed, "Base"), ["Base", "Shared"]);
+    }
+
+    #[test]
+    fn abort_on_an_unmanaged_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let addons_path = dir.path().join("AddOns");
+        write_folder(&addons_path, "Plugin", "by hand");
+        let archive_path = dir.path().join("Plugin.zip");
+        write_archive(&archive_path, &["Plugin"], "Plugin");
+
+        let result = install_archive(
+            &addons_path,
+            &dir.path().join("WTF"),
+            ReleaseType::Retail,
+            &archive_path,
+            addon("Plugin", "1", &[]),
+            &mut Vec::new(),
+            ConflictResolution::Abort,
+        );
+
+        let Err(InstallError::Conflicts(conflicts)) = result else {
+            panic!("the install wasn't refused");
+        };
+        assert!(matches!(conflicts[0].kind, ConflictKind::Unmanaged));
+        assert_eq!(read_source(&addons_path, "Plugin"), "by hand");
+    }
+
+    #[test]
+    fn replace_takes_the_folder_over() {
+        let (dir, managed, result) =
+            install_over_base(&["Plugin", "Shared"], ConflictResolution::Replace);
+        let addons_path = dir.path().join("Interface").join("AddOns");
+
+        let report = result.unwrap_or_else(|error| panic!("{error}"));
+        assert_eq!(report.installed_folders, ["Plugin", "Shared"]);
+        assert!(report.skipped_folders.is_empty());
+        assert_eq!(read_source(&addons_path, "Shared"), "Plugin");
+        assert_eq!(read_source(&addons_path, "Base"), "Base");
+        assert_eq!(get_folders(&managed, "Base"), ["Base"]);
+        assert_eq!(get_folders(&managed, "Plugin"), ["Plugin", "Shared"]);
+    }
+
+    #[test]
+    fn keep_existing_skips_the_folder() {
+        let (dir, managed, result) =
+            install_over_base(&["Plugin", "Shared"], ConflictResolution::KeepExisting);
+        let addons_path = dir.path().join("Interface").join("AddOns");
+
+        let report = result.unwrap_or_else(|error| panic!("{error}"));
+        assert_eq!(report.installed_folders, ["Plugin"]);
+        assert_eq!(report.skipped_folders, ["Shared"]);
+        assert_eq!(read_source(&addons_path, "Shared"), "Base");
+        assert_eq!(read_source(&addons_path, "Plugin"), "Plugin");
+        assert_eq!(get_folders(&managed, "Base"), ["Base", "Shared"]);
+        assert_eq!(get_folders(&managed, "Plugin"), ["Plugin"]);
+    }
+
+    #[test]
+    fn update_removes_folders_it_no_longer_ships() {
+        let dir = tempfile::tempdir().unwrap();
+        let addons_path = dir.path().join("Interface").join("AddOns");
+        write_folder(&addons_path, "Plugin", "Plugin 1");
+        write_folder(&addons_path, "Plugin_Old", "Plugin 1");
+        let mut managed = vec![addon("Plugin", "1", &["Plugin", "Plugin_Old"])];
+        let archive_path = dir.path().join("Plugin.zip");
+        write_archive(&archive_path, &["Plugin"], "Plugin 2");
+
+        let report = install_archive(
+            &addons_path,
+            &dir.path().join("WTF"),
+            ReleaseType::Retail,
+            &archive_path,
+            addon("Plugin", "2", &[]),
+            &mut managed,
+            ConflictResolution::Abort,
+        )
+        .unwrap_or_else(|error| panic!("{error}"));
+
+        assert_eq!(report.removed_folders, ["Plugin_Old"]);
+        assert!(!addons_path.join("Plugin_Old").exists());
+        assert_eq!(read_source(&addons_path, "Plugin"), "Plugin 2");
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].version_id, "2");
+        assert_eq!(managed[0].folders, ["Plugin"]);
+    }
+
+    #[test]
+    fn staging_stays_out_of_the_addons_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let interface_path = dir.path().join("Interface");
+        let addons_path = interface_path.join("AddOns");
+        fs::create_dir_all(&addons_path).unwrap();
+        // Left behind by an install that was interrupted.
+        write_folder(&interface_path.join(STAGING_FOLDER), "Leftover", "crash");
+        let archive_path = dir.path().join("Plugin.zip");
+        write_archive(&archive_path, &["Plugin"], "Plugin");
+
+        install_archive(
+            &addons_path,
+            &dir.path().join("WTF"),
+            ReleaseType::Retail,
+            &archive_path,
+            addon("Plugin", "1", &[]),
+            &mut Vec::new(),
+            ConflictResolution::Abort,
+        )
+        .unwrap_or_else(|error| panic!("{error}"));
+
+        let names: Vec<String> = fs::read_dir(&addons_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names, ["Plugin"]);
+        assert!(!interface_path.join(STAGING_FOLDER).exists());
+    }
+}
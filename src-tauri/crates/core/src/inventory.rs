@@ -0,0 +1,91 @@
+use std::{fs, path::Path};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    conflicts::{self, FolderConflict},
+    game::{self, ReleaseType},
+    toc::Toc,
+    AppState,
+};
+
+/// An addon folder found in `Interface/AddOns`.
+#[derive(Clone, Serialize)]
+pub struct InstalledAddon {
+    pub folder: String,
+    pub toc: Toc,
+}
+
+#[derive(Serialize)]
+pub struct Inventory {
+    /// The client's interface number, if the game version could be read.
+    pub client_interface: Option<u32>,
+    pub addons: Vec<InventoryEntry>,
+    pub conflicts: Vec<FolderConflict>,
+}
+
+#[derive(Serialize)]
+pub struct InventoryEntry {
+    #[serde(flatten)]
+    pub addon: InstalledAddon,
+    /// The name of the managed addon that installed this folder, if any.
+    pub managed_by: Option<String>,
+    /// Whether the addon's `## Interface` is older than the client's.
+    pub out_of_date: bool,
+}
+
+/// Reads every addon folder with a TOC for `release_type`, sorted by folder name.
+pub fn scan(addons_path: &Path, release_type: ReleaseType) -> Vec<InstalledAddon> {
+    let mut installed = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(addons_path) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some(toc) = Toc::read(&path, release_type) {
+                installed.push(InstalledAddon {
+                    folder: toc.folder.clone(),
+                    toc,
+                });
+            }
+        }
+    }
+
+    installed.sort_by_key(|addon| addon.folder.to_ascii_lowercase());
+    installed
+}
+
+#[tauri::command]
+pub fn get_inventory(state: State<AppState>, release_type: ReleaseType) -> Option<Inventory> {
+    let (addons_path, client_interface) = {
+        let config = state.config.lock().ok()?;
+        (
+            game::get_addons_path(&config, release_type)?,
+            game::read_game_version(&config, release_type)
+                .and_then(|version| game::get_interface_version(&version)),
+        )
+    };
+    let installed = scan(&addons_path, release_type);
+    let addons = state.addons.lock().ok()?;
+    let managed = addons.get_list(release_type);
+
+    Some(Inventory {
+        client_interface,
+        conflicts: conflicts::find_conflicts(&installed, managed),
+        addons: installed
+            .into_iter()
+            .map(|addon| InventoryEntry {
+                managed_by: managed
+                    .iter()
+                    .find(|managed| managed.claims_folder(&addon.folder))
+                    .map(|managed| managed.name.clone()),
+                out_of_date: client_interface
+                    .is_some_and(|client_interface| addon.toc.is_out_of_date(client_interface)),
+                addon,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,334 @@
+use std::{path::Path, process::Command, thread};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+    conflicts::ConflictResolution,
+    data::{addons::AddonData, config::LaunchCommand},
+    game::{self, ReleaseType},
+    installer, AppState,
+};
+
+/// An archive the frontend downloaded for one of the addons from `get_addons_to_check`.
+#[derive(Deserialize)]
+pub struct DownloadedUpdate {
+    pub archive_path: String,
+    /// The addon with the version id of the archive.
+    pub addon: AddonData,
+}
+
+#[derive(Serialize)]
+pub struct FailedUpdate {
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Default, Serialize)]
+pub struct LaunchReport {
+    pub updated: Vec<String>,
+    pub failed: Vec<FailedUpdate>,
+    /// The managed addons no archive was given for, which still have the version that was
+    /// installed before.
+    pub unchanged: Vec<String>,
+}
+
+/// Whether `command` is a URI like `lutris:rungameid/3`. A Windows path like `C:\Games` has a
+/// one letter scheme, so it isn't one.
+fn is_uri(command: &str) -> bool {
+    let Some((scheme, _)) = command.split_once(':') else {
+        return false;
+    };
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Splits a command line on whitespace, keeping quoted parts together. Backslashes are kept as
+/// they are, since the command often contains Windows paths for Wine.
+fn split_command_line(command: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+
+    for c in command.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => parts.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("the launch command has an unclosed quote"));
+    }
+    parts.extend(current);
+    Ok(parts)
+}
+
+/// Encodes everything but unreserved characters, for use inside a URI path.
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// The URI to open for `launch_command`. Steam takes the game's arguments in the URI, as
+/// `steam://run/<id>//<arguments>/`. Other launchers only take them from their own settings, so
+/// arguments for them are an error rather than being dropped.
+fn build_uri(launch_command: &LaunchCommand) -> Result<String, String> {
+    let command = launch_command.command.trim();
+    if launch_command.arguments.is_empty() {
+        return Ok(command.to_owned());
+    }
+
+    let app_id = command
+        .strip_prefix("steam://rungameid/")
+        .or_else(|| command.strip_prefix("steam://run/"))
+        .map(|app_id| app_id.trim_end_matches('/'));
+    match app_id {
+        Some(app_id) if !app_id.is_empty() && app_id.chars().all(|c| c.is_ascii_digit()) => {
+            Ok(format!(
+                "steam://run/{app_id}//{}/",
+                percent_encode(&launch_command.arguments.join(" "))
+            ))
+        }
+        _ => Err(String::from(
+            "arguments can't be passed through this launcher's URI, set them in the launcher \
+             instead",
+        )),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn open_uri(uri: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.args(["/C", "start", "", uri]);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn open_uri(uri: &str) -> Command {
+    let mut command = Command::new("open");
+    command.arg(uri);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn open_uri(uri: &str) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(uri);
+    command
+}
+
+/// Starts the game without waiting for it to exit.
+pub fn launch(launch_command: &LaunchCommand) -> Result<(), String> {
+    let mut command = if is_uri(&launch_command.command) {
+        open_uri(&build_uri(launch_command)?)
+    } else {
+        let mut parts = split_command_line(&launch_command.command)?.into_iter();
+        let program = parts
+            .next()
+            .ok_or_else(|| String::from("the launch command is empty"))?;
+        let mut command = Command::new(program);
+        command.args(parts).args(&launch_command.arguments);
+        command
+    };
+
+    let mut child = command.spawn().map_err(|error| error.to_string())?;
+    // Waited on so the process doesn't linger as a zombie once it exits.
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+fn read_launch_command(
+    state: &AppState,
+    release_type: ReleaseType,
+) -> Result<LaunchCommand, String> {
+    match state.config.lock() {
+        Ok(config) => config.get_launch_command(release_type).clone(),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no launch command is set"))
+}
+
+#[tauri::command]
+pub fn launch_game(state: State<AppState>, release_type: ReleaseType) -> Result<(), String> {
+    launch(&read_launch_command(&state, release_type)?)
+}
+
+/// The names of the managed addons none of `updates` is for. An update may rename its addon,
+/// so they are matched by project rather than by name.
+fn list_unchanged(managed: &[AddonData], updates: &[DownloadedUpdate]) -> Vec<String> {
+    managed
+        .iter()
+        .filter(|addon| {
+            !updates
+                .iter()
+                .any(|update| update.addon.is_same_addon(addon))
+        })
+        .map(|addon| addon.name.clone())
+        .collect()
+}
+
+/// Installs the archives the frontend downloaded and then launches the game. WAM doesn't query
+/// the providers itself, so updating everything means checking every addon from
+/// `get_addons_to_check` first; the managed addons without an archive are listed as unchanged.
+/// Updates that fail are reported, but don't stop the launch.
+#[tauri::command]
+pub fn install_and_launch_game(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    updates: Vec<DownloadedUpdate>,
+) -> Result<LaunchReport, String> {
+    let launch_command = read_launch_command(&state, release_type)?;
+    if game::is_game_running() {
+        return Err(String::from(
+            "World of Warcraft is already running, close it before updating",
+        ));
+    }
+
+    let mut report = LaunchReport::default();
+    if let Ok(addons) = state.addons.lock() {
+        report.unchanged = list_unchanged(addons.get_list(release_type), &updates);
+    }
+    for update in updates {
+        let name = update.addon.name.clone();
+        match installer::install(
+            &state,
+            release_type,
+            Path::new(&update.archive_path),
+            update.addon,
+            ConflictResolution::Abort,
+        ) {
+            Ok(_) => report.updated.push(name),
+            Err(error) => report.failed.push(FailedUpdate {
+                name,
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    launch(&launch_command)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{
+        addons::{AddonData, AddonProvider, ReleaseChannel},
+        config::LaunchCommand,
+    };
+
+    use super::{build_uri, is_uri, list_unchanged, split_command_line, DownloadedUpdate};
+
+    fn launch_command(command: &str, arguments: &[&str]) -> LaunchCommand {
+        LaunchCommand {
+            command: command.to_owned(),
+            arguments: arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn uris() {
+        assert!(is_uri("lutris:rungameid/3"));
+        assert!(is_uri("steam://rungameid/12345"));
+        assert!(is_uri("heroic+1://launch"));
+        assert!(!is_uri(
+            "C:\\Program Files (x86)\\World of Warcraft\\Wow.exe"
+        ));
+        assert!(!is_uri("wine C:\\Games\\Wow.exe"));
+        assert!(!is_uri("/usr/bin/wine"));
+        assert!(!is_uri(""));
+    }
+
+    #[test]
+    fn command_lines() {
+        assert_eq!(
+            split_command_line("wine \"/games/World of Warcraft/_retail_/Wow.exe\" -console")
+                .unwrap(),
+            [
+                "wine",
+                "/games/World of Warcraft/_retail_/Wow.exe",
+                "-console"
+            ]
+        );
+        assert_eq!(
+            split_command_line(r#"wine64 'C:\Program Files\Wow.exe'  C:\Games\x"y z"\"#).unwrap(),
+            ["wine64", r"C:\Program Files\Wow.exe", r"C:\Games\xy z\"]
+        );
+        assert_eq!(split_command_line("run \"\"").unwrap(), ["run", ""]);
+    }
+
+    #[test]
+    fn empty_command_lines() {
+        assert!(split_command_line("").unwrap().is_empty());
+        assert!(split_command_line(" \t ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        assert!(split_command_line("wine \"/games/Wow.exe").is_err());
+    }
+
+    #[test]
+    fn uri_arguments() {
+        assert_eq!(
+            build_uri(&launch_command("lutris:rungameid/3", &[])).unwrap(),
+            "lutris:rungameid/3"
+        );
+        assert_eq!(
+            build_uri(&launch_command(
+                "steam://rungameid/12345",
+                &["-console", "-launcherlogin"]
+            ))
+            .unwrap(),
+            "steam://run/12345//-console%20-launcherlogin/"
+        );
+        assert_eq!(
+            build_uri(&launch_command("steam://run/12345/", &["-a/b"])).unwrap(),
+            "steam://run/12345//-a%2Fb/"
+        );
+        assert!(build_uri(&launch_command("lutris:rungameid/3", &["-console"])).is_err());
+        assert!(build_uri(&launch_command("steam://open/games", &["-console"])).is_err());
+    }
+
+    #[test]
+    fn unchanged_addons_are_matched_by_project() {
+        let addon = |name: &str, project_id: &str| AddonData {
+            name: name.to_owned(),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: Some(project_id.to_owned()),
+            folders: Vec::new(),
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        };
+        let managed = [addon("Details", "61284"), addon("Deadly Boss Mods", "3358")];
+        let updates = [DownloadedUpdate {
+            archive_path: String::from("/tmp/details.zip"),
+            addon: addon("Details! Damage Meter", "61284"),
+        }];
+
+        assert_eq!(list_unchanged(&managed, &updates), ["Deadly Boss Mods"]);
+    }
+}
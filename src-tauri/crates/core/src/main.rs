@@ -3,41 +3,135 @@
 
 use std::sync::{Arc, Mutex};
 
-use data::{addons::Addons, config::AppConfig, LocalSaveData};
-use tauri::Window;
+use data::{
+    addons::Addons, config::AppConfig, profiles::Profiles, queue::OperationQueue,
+    state::PersistentState, LocalSaveData, StartupDiagnostic,
+};
+use tauri::{State, Window};
 
+mod addon_list;
+mod addon_pack;
+mod conflicts;
 mod curseforge_window;
 mod data;
+mod dependencies;
 mod game;
+mod installer;
+mod inventory;
+mod launcher;
+mod operations;
+mod profiles;
+mod toc;
+mod updates;
+mod wtf;
 
 struct AppState {
     config: Mutex<AppConfig>,
     addons: Mutex<Addons>,
+    persistent_state: Mutex<PersistentState>,
+    profiles: Mutex<Profiles>,
+    detected_patches: Mutex<Vec<updates::DetectedPatch>>,
+    operation_queue: Mutex<OperationQueue>,
     curseforge_window: Arc<Mutex<Option<Window>>>,
+    startup_diagnostics: Vec<StartupDiagnostic>,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        match (AppConfig::load(), Addons::load()) {
-            (Ok(config), Ok(addons)) => Self {
-                config: Mutex::new(config),
-                addons: Mutex::new(addons),
-                curseforge_window: Arc::new(Mutex::new(None)),
-            },
-            _ => todo!(),
+        let mut startup_diagnostics = Vec::new();
+        let config = AppConfig::load_or_recover(&mut startup_diagnostics);
+        let addons = Addons::load_or_recover(&mut startup_diagnostics);
+        let persistent_state = PersistentState::load_or_recover(&mut startup_diagnostics);
+        let profiles = Profiles::load_or_recover(&mut startup_diagnostics);
+        let operation_queue = OperationQueue::load_or_recover(&mut startup_diagnostics);
+        data::log_startup_diagnostics(&startup_diagnostics);
+
+        Self {
+            config: Mutex::new(config),
+            addons: Mutex::new(addons),
+            persistent_state: Mutex::new(persistent_state),
+            profiles: Mutex::new(profiles),
+            detected_patches: Mutex::new(Vec::new()),
+            operation_queue: Mutex::new(operation_queue),
+            curseforge_window: Arc::new(Mutex::new(None)),
+            startup_diagnostics,
         }
     }
 }
 
+/// Problems that were recovered from while loading WAM's files at launch.
+#[tauri::command]
+fn get_startup_diagnostics(state: State<AppState>) -> Vec<StartupDiagnostic> {
+    state.startup_diagnostics.clone()
+}
+
 fn main() {
     tauri::Builder::default()
         .manage(AppState::new())
+        .setup(|app| {
+            updates::detect_patches(&app.handle());
+            data::watcher::watch_data_files(app.handle());
+            operations::watch_game(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
+            addon_list::export_addon_list,
+            addon_list::import_addon_list,
+            addon_pack::encode_addon_pack,
+            addon_pack::import_addon_pack,
+            addon_pack::preview_addon_pack,
             curseforge_window::create_curseforge_window,
+            data::config::clear_launch_command,
             data::config::get_game_path,
+            data::config::get_launch_command,
+            data::config::get_running_game_policy,
             data::config::set_game_path,
+            data::config::set_launch_command,
+            data::config::set_running_game_policy,
             data::config::save_config,
+            data::location::get_data_location,
+            dependencies::get_dependency_report,
+            game::get_game_state,
             game::get_game_version,
+            get_startup_diagnostics,
+            installer::install_addon_archive,
+            inventory::get_inventory,
+            launcher::install_and_launch_game,
+            launcher::launch_game,
+            operations::cancel_queued_operation,
+            operations::get_queued_operations,
+            operations::retry_queued_operation,
+            profiles::capture_profile,
+            profiles::delete_profile,
+            profiles::get_profiles,
+            profiles::save_profile,
+            profiles::switch_profile,
+            updates::get_detected_patches,
+            updates::get_addons_to_check,
+            wtf::accounts::get_wtf_accounts,
+            wtf::accounts::list_wtf_accounts,
+            wtf::accounts::list_wtf_characters,
+            wtf::accounts::list_wtf_realms,
+            wtf::addons_txt::get_addon_enablement,
+            wtf::addons_txt::set_addons_enabled,
+            wtf::bindings::backup_bindings,
+            wtf::bindings::diff_bindings,
+            wtf::bindings::get_bindings,
+            wtf::bindings::get_macros,
+            wtf::bindings::restore_bindings,
+            wtf::copy::copy_wtf_settings,
+            wtf::cvars::get_cvars,
+            wtf::cvars::set_cvars,
+            wtf::saved_variables::browse_saved_variables,
+            wtf::saved_variables::get_saved_variables_report,
+            wtf::saved_variables::get_saved_variables_sizes,
+            wtf::saved_variables::remove_orphaned_saved_variables,
+            wtf::saved_variables::search_saved_variables,
+            wtf::snapshots::create_snapshot,
+            wtf::snapshots::delete_snapshot,
+            wtf::snapshots::diff_snapshot,
+            wtf::snapshots::list_snapshots,
+            wtf::snapshots::restore_snapshot,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,420 @@
+use std::{collections::BTreeMap, path::Path, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+    conflicts::ConflictResolution,
+    data::{
+        addons::AddonData,
+        config::RunningGamePolicy,
+        queue::{OperationQueue, QueuedOperation},
+        LocalSaveData,
+    },
+    game::{self, ReleaseType},
+    installer, profiles,
+    wtf::{self, addons_txt, cvars, WtfTarget},
+    AppState,
+};
+
+/// How often `/proc` is checked for the game having exited while operations are queued.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Work that was requested while the game was running and waits for it to exit.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Operation {
+    Install {
+        release_type: ReleaseType,
+        archive_path: String,
+        addon: AddonData,
+        resolution: Option<ConflictResolution>,
+    },
+    SwitchProfile {
+        release_type: ReleaseType,
+        name: String,
+        target: WtfTarget,
+    },
+    SetAddonsEnabled {
+        release_type: ReleaseType,
+        target: Option<WtfTarget>,
+        folders: Vec<String>,
+        enabled: bool,
+    },
+    SetCvars {
+        release_type: ReleaseType,
+        account: Option<String>,
+        cvars: BTreeMap<String, String>,
+    },
+}
+
+/// A queued operation that was run after the game exited.
+#[derive(Clone, Serialize)]
+pub struct FinishedOperation {
+    pub operation: Operation,
+    pub error: Option<String>,
+}
+
+/// Sent as `queued-operations-finished` after the queue was run.
+#[derive(Clone, Serialize)]
+struct QueueRunEvent {
+    finished: Vec<FinishedOperation>,
+    /// Why the queue couldn't be run or saved, if it couldn't.
+    error: Option<String>,
+}
+
+/// The result of a command that may have been queued instead of run.
+#[derive(Serialize)]
+#[serde(tag = "status", content = "result")]
+pub enum Guarded<T> {
+    Done(T),
+    Queued,
+}
+
+impl Operation {
+    pub fn run(&self, state: &AppState) -> Result<(), String> {
+        match self {
+            Operation::Install {
+                release_type,
+                archive_path,
+                addon,
+                resolution,
+            } => installer::install(
+                state,
+                *release_type,
+                Path::new(archive_path),
+                addon.clone(),
+                resolution.unwrap_or_default(),
+            )
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+            Operation::SwitchProfile {
+                release_type,
+                name,
+                target,
+            } => profiles::switch(state, *release_type, name, target).map(|_| ()),
+            Operation::SetAddonsEnabled {
+                release_type,
+                target,
+                folders,
+                enabled,
+            } => addons_txt::set_enabled_for(
+                state,
+                *release_type,
+                target.as_ref(),
+                folders,
+                *enabled,
+            )
+            .map(|_| ()),
+            Operation::SetCvars {
+                release_type,
+                account,
+                cvars: values,
+            } => cvars::set_all(state, *release_type, account.as_deref(), values),
+        }
+    }
+}
+
+/// Checks whether the game is running before an install or WTF change. If it is, the
+/// configured [`RunningGamePolicy`] either fails with an error or queues the operation built by
+/// `operation`, in which case `true` is returned and the caller must not do the work itself.
+pub fn queue_if_running(
+    state: &AppState,
+    operation: impl FnOnce() -> Operation,
+) -> Result<bool, String> {
+    if !game::is_game_running() {
+        return Ok(false);
+    }
+    let policy = match state.config.lock() {
+        Ok(config) => *config.get_running_game_policy(),
+        Err(error) => return Err(error.to_string()),
+    };
+
+    match policy {
+        RunningGamePolicy::Block => {
+            wtf::ensure_game_closed()?;
+            Ok(false)
+        }
+        RunningGamePolicy::Queue => {
+            let mut queue = state
+                .operation_queue
+                .lock()
+                .map_err(|error| error.to_string())?;
+            queue.get_list_mut().push(QueuedOperation::new(operation()));
+            if let Err(error) = queue.save() {
+                queue.get_list_mut().pop();
+                return Err(error.to_string());
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Runs the operations of `queue` that haven't failed, in order, while `can_run` allows it.
+/// Each one is removed from the queue, or kept and marked as failed, and the queue is `save`d
+/// as soon as it finishes, so a crash never loses or repeats more than one operation.
+fn run_pending(
+    queue: &mut OperationQueue,
+    can_run: impl Fn() -> bool,
+    run: impl Fn(&Operation) -> Result<(), String>,
+    save: impl Fn(&OperationQueue) -> Result<(), String>,
+) -> Result<Vec<FinishedOperation>, String> {
+    let mut finished = Vec::new();
+    let mut index = 0;
+    while index < queue.get_list().len() {
+        if queue.get_list()[index].error.is_some() {
+            index += 1;
+            continue;
+        }
+        if !can_run() {
+            break;
+        }
+
+        let operation = queue.get_list()[index].operation.clone();
+        let error = run(&operation).err();
+        match &error {
+            Some(error) => {
+                queue.get_list_mut()[index].error = Some(error.clone());
+                index += 1;
+            }
+            None => {
+                queue.get_list_mut().remove(index);
+            }
+        }
+        save(queue)?;
+        finished.push(FinishedOperation { operation, error });
+    }
+    Ok(finished)
+}
+
+/// Runs the queued operations in the order they were requested, stopping if the game is
+/// started again. An operation that fails doesn't stop the ones after it, and stays queued
+/// with its error until it is retried or cancelled.
+pub fn run_queued(state: &AppState) -> Result<Vec<FinishedOperation>, String> {
+    // Held throughout, so the queue can't be changed under the run.
+    let mut queue = state
+        .operation_queue
+        .lock()
+        .map_err(|error| error.to_string())?;
+    run_pending(
+        &mut queue,
+        || !game::is_game_running(),
+        |operation| operation.run(state),
+        |queue| queue.save().map_err(|error| error.to_string()),
+    )
+}
+
+/// Watches `/proc` for the game and runs the queue whenever it isn't running, so operations
+/// queued before WAM was closed run at the next launch too. The frontend is told through the
+/// `queued-operations-finished` event.
+pub fn watch_game(app_handle: AppHandle) {
+    thread::spawn(move || {
+        // Repeated every poll otherwise, e.g. while the queue file can't be saved.
+        let mut last_error = None;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let state = app_handle.state::<AppState>();
+            let has_pending = match state.operation_queue.lock() {
+                Ok(queue) => queue.has_pending(),
+                Err(_) => false,
+            };
+            if !has_pending {
+                continue;
+            }
+
+            let (finished, error) = match run_queued(&state) {
+                Ok(finished) => (finished, None),
+                Err(error) => (Vec::new(), Some(error)),
+            };
+            if finished.is_empty() && (error.is_none() || error == last_error) {
+                continue;
+            }
+            last_error = error.clone();
+            let _ = app_handle.emit_all(
+                "queued-operations-finished",
+                QueueRunEvent { finished, error },
+            );
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_queued_operations(state: State<AppState>) -> Vec<QueuedOperation> {
+    match state.operation_queue.lock() {
+        Ok(queue) => queue.get_list().clone(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Removes the queued operation at `index` without running it.
+#[tauri::command]
+pub fn cancel_queued_operation(state: State<AppState>, index: usize) -> Result<Operation, String> {
+    let mut queue = state
+        .operation_queue
+        .lock()
+        .map_err(|error| error.to_string())?;
+    if index >= queue.get_list().len() {
+        return Err(String::from("there is no such queued operation"));
+    }
+    let queued = queue.get_list_mut().remove(index);
+    queue.save().map_err(|error| error.to_string())?;
+    Ok(queued.operation)
+}
+
+/// Clears the error of the failed operation at `index`, so it is run again once the game isn't
+/// running.
+#[tauri::command]
+pub fn retry_queued_operation(state: State<AppState>, index: usize) -> Result<(), String> {
+    let mut queue = state
+        .operation_queue
+        .lock()
+        .map_err(|error| error.to_string())?;
+    let Some(queued) = queue.get_list_mut().get_mut(index) else {
+        return Err(String::from("there is no such queued operation"));
+    };
+    let error = queued.error.take();
+    if let Err(save_error) = queue.save() {
+        queue.get_list_mut()[index].error = error;
+        return Err(save_error.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    use crate::{
+        data::{
+            queue::{OperationQueue, QueuedOperation},
+            LocalSaveData,
+        },
+        game::ReleaseType,
+    };
+
+    use super::{run_pending, Operation};
+
+    fn set_cvar(name: &str) -> Operation {
+        Operation::SetCvars {
+            release_type: ReleaseType::Retail,
+            account: None,
+            cvars: BTreeMap::from([(name.to_owned(), String::from("1"))]),
+        }
+    }
+
+    fn get_name(operation: &Operation) -> String {
+        match operation {
+            Operation::SetCvars { cvars, .. } => cvars.keys().next().unwrap().clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn queue_of(names: &[&str]) -> OperationQueue {
+        let mut queue = OperationQueue::new();
+        for name in names {
+            queue
+                .get_list_mut()
+                .push(QueuedOperation::new(set_cvar(name)));
+        }
+        queue
+    }
+
+    /// The names and errors of the queued operations.
+    fn describe(queue: &OperationQueue) -> Vec<(String, Option<String>)> {
+        queue
+            .get_list()
+            .iter()
+            .map(|queued| (get_name(&queued.operation), queued.error.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn each_operation_is_saved_as_it_finishes() {
+        let mut queue = queue_of(&["first", "broken", "last"]);
+        let saved = RefCell::new(Vec::new());
+
+        let finished = run_pending(
+            &mut queue,
+            || true,
+            |operation| match get_name(operation).as_str() {
+                "broken" => Err(String::from("failed")),
+                _ => Ok(()),
+            },
+            |queue| {
+                saved.borrow_mut().push(describe(queue));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let failed = (String::from("broken"), Some(String::from("failed")));
+        let last = (String::from("last"), None);
+        assert_eq!(
+            saved.into_inner(),
+            [
+                vec![(String::from("broken"), None), last.clone()],
+                vec![failed.clone(), last],
+                vec![failed.clone()],
+            ]
+        );
+        let finished: Vec<(String, Option<String>)> = finished
+            .iter()
+            .map(|finished| (get_name(&finished.operation), finished.error.clone()))
+            .collect();
+        assert_eq!(finished[1], failed);
+        assert_eq!(describe(&queue), [failed]);
+        assert!(!queue.has_pending());
+    }
+
+    #[test]
+    fn failed_operations_are_not_run_again() {
+        let mut queue = queue_of(&["broken"]);
+        queue.get_list_mut()[0].error = Some(String::from("failed"));
+
+        let finished = run_pending(
+            &mut queue,
+            || true,
+            |_| panic!("a failed operation was run"),
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert!(finished.is_empty());
+        assert_eq!(queue.get_list().len(), 1);
+    }
+
+    #[test]
+    fn running_stops_when_the_game_starts() {
+        let mut queue = queue_of(&["first", "second"]);
+        let runs = RefCell::new(0);
+
+        let finished = run_pending(
+            &mut queue,
+            || *runs.borrow() == 0,
+            |_| {
+                *runs.borrow_mut() += 1;
+                Ok(())
+            },
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(finished.len(), 1);
+        assert_eq!(describe(&queue), [(String::from("second"), None)]);
+    }
+
+    #[test]
+    fn a_failed_save_stops_running() {
+        let mut queue = queue_of(&["first", "second"]);
+
+        let result = run_pending(
+            &mut queue,
+            || true,
+            |_| Ok(()),
+            |_| Err(String::from("disk full")),
+        );
+
+        assert_eq!(result.err().as_deref(), Some("disk full"));
+        assert_eq!(describe(&queue), [(String::from("second"), None)]);
+    }
+}
@@ -0,0 +1,337 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    data::{
+        addons::AddonData,
+        profiles::{Profile, Profiles},
+        LocalSaveData,
+    },
+    game::{self, ReleaseType},
+    inventory::{self, InstalledAddon},
+    operations::{self, Guarded, Operation},
+    wtf::{
+        self,
+        addons_txt::{self, AddonsTxt},
+        WtfTarget,
+    },
+    AppState,
+};
+
+/// What switching to a profile changed in `AddOns.txt`.
+#[derive(Default, Serialize)]
+pub struct ProfileSwitch {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+    /// Addons in the profile that aren't installed anymore.
+    pub missing: Vec<String>,
+}
+
+/// Records which installed addons `addons_txt` enables, splitting them into managed addons
+/// and loose folders.
+pub fn capture(
+    name: String,
+    installed: &[InstalledAddon],
+    managed: &[AddonData],
+    addons_txt: &AddonsTxt,
+) -> Profile {
+    let enabled_folders: Vec<&str> = installed
+        .iter()
+        .filter(|addon| {
+            addons_txt
+                .is_enabled(&addon.folder)
+                .unwrap_or(!addon.toc.is_disabled_by_default())
+        })
+        .map(|addon| addon.folder.as_str())
+        .collect();
+
+    let mut profile = Profile {
+        name,
+        managed: Vec::new(),
+        unmanaged: Vec::new(),
+    };
+    for addon in managed {
+        if enabled_folders
+            .iter()
+            .any(|folder| addon.claims_folder(folder))
+        {
+            profile.managed.push(addon.name.clone());
+        }
+    }
+    for folder in enabled_folders {
+        if !managed.iter().any(|addon| addon.claims_folder(folder)) {
+            profile.unmanaged.push(folder.to_owned());
+        }
+    }
+    profile
+}
+
+/// Enables exactly the installed folders that belong to `profile` and disables the rest.
+pub fn apply(
+    profile: &Profile,
+    installed: &[InstalledAddon],
+    managed: &[AddonData],
+    addons_txt: &mut AddonsTxt,
+) -> ProfileSwitch {
+    let mut switch = ProfileSwitch::default();
+    let mut folders: Vec<&str> = profile.unmanaged.iter().map(String::as_str).collect();
+
+    for name in &profile.managed {
+        match managed.iter().find(|addon| &addon.name == name) {
+            Some(addon) => folders.extend(addon.folders.iter().map(String::as_str)),
+            None => switch.missing.push(name.clone()),
+        }
+    }
+    for folder in &profile.unmanaged {
+        if !installed
+            .iter()
+            .any(|addon| addon.folder.eq_ignore_ascii_case(folder))
+        {
+            switch.missing.push(folder.clone());
+        }
+    }
+
+    for addon in installed {
+        let enabled = folders
+            .iter()
+            .any(|folder| folder.eq_ignore_ascii_case(&addon.folder));
+        addons_txt.set_enabled(&addon.folder, enabled);
+        if enabled {
+            switch.enabled.push(addon.folder.clone());
+        } else {
+            switch.disabled.push(addon.folder.clone());
+        }
+    }
+    switch
+}
+
+/// The `AddOns.txt` of `target` and the installed addons of `release_type`.
+fn read_target(
+    state: &AppState,
+    release_type: ReleaseType,
+    target: &WtfTarget,
+) -> Result<(PathBuf, Vec<InstalledAddon>), String> {
+    let wtf_path = wtf::get_configured_wtf_path(state, release_type)?;
+    let addons_path = match state.config.lock() {
+        Ok(config) => game::get_addons_path(&config, release_type),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no game path is set"))?;
+
+    Ok((
+        addons_txt::get_path(&wtf_path, target)?,
+        inventory::scan(&addons_path, release_type),
+    ))
+}
+
+fn save_profiles(profiles: &Profiles) -> Result<(), String> {
+    profiles.save().map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+pub fn get_profiles(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Result<Vec<Profile>, String> {
+    match state.profiles.lock() {
+        Ok(profiles) => Ok(profiles.get_list(release_type).clone()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Adds `profile`, or replaces the profile with the same name.
+#[tauri::command]
+pub fn save_profile(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    profile: Profile,
+) -> Result<(), String> {
+    let mut profiles = state.profiles.lock().map_err(|error| error.to_string())?;
+    let list = profiles.get_list_mut(release_type);
+    match list
+        .iter_mut()
+        .find(|existing| existing.name == profile.name)
+    {
+        Some(existing) => *existing = profile,
+        None => list.push(profile),
+    }
+    save_profiles(&profiles)
+}
+
+/// Saves the addons that are enabled for `target` right now as the profile `name`.
+#[tauri::command]
+pub fn capture_profile(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    name: String,
+    target: WtfTarget,
+) -> Result<Profile, String> {
+    let (path, installed) = read_target(&state, release_type, &target)?;
+    let addons_txt = AddonsTxt::read(&path).map_err(|error| error.to_string())?;
+    let profile = match state.addons.lock() {
+        Ok(addons) => capture(name, &installed, addons.get_list(release_type), &addons_txt),
+        Err(error) => return Err(error.to_string()),
+    };
+
+    save_profile(state, release_type, profile.clone())?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn delete_profile(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    name: String,
+) -> Result<(), String> {
+    let mut profiles = state.profiles.lock().map_err(|error| error.to_string())?;
+    profiles
+        .get_list_mut(release_type)
+        .retain(|profile| profile.name != name);
+    save_profiles(&profiles)
+}
+
+/// Rewrites the `AddOns.txt` of `target` so that only the addons in the profile are enabled.
+pub fn switch(
+    state: &AppState,
+    release_type: ReleaseType,
+    name: &str,
+    target: &WtfTarget,
+) -> Result<ProfileSwitch, String> {
+    let profile = match state.profiles.lock() {
+        Ok(profiles) => profiles.get(release_type, name).cloned(),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| format!("there is no profile called '{name}'"))?;
+
+    let (path, installed) = read_target(state, release_type, target)?;
+    let mut addons_txt = AddonsTxt::read(&path).map_err(|error| error.to_string())?;
+    let switch = match state.addons.lock() {
+        Ok(addons) => apply(
+            &profile,
+            &installed,
+            addons.get_list(release_type),
+            &mut addons_txt,
+        ),
+        Err(error) => return Err(error.to_string()),
+    };
+
+    addons_txt.write(&path).map_err(|error| error.to_string())?;
+    Ok(switch)
+}
+
+#[tauri::command]
+pub fn switch_profile(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    name: String,
+    target: WtfTarget,
+) -> Result<Guarded<ProfileSwitch>, String> {
+    if operations::queue_if_running(&state, || Operation::SwitchProfile {
+        release_type,
+        name: name.clone(),
+        target: target.clone(),
+    })? {
+        return Ok(Guarded::Queued);
+    }
+    switch(&state, release_type, &name, &target).map(Guarded::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{
+            addons::{AddonData, AddonProvider, ReleaseChannel},
+            profiles::Profile,
+        },
+        inventory::InstalledAddon,
+        toc::Toc,
+        wtf::addons_txt::AddonsTxt,
+    };
+
+    use super::{apply, capture};
+
+    fn addon(name: &str, folders: &[&str]) -> AddonData {
+        AddonData {
+            name: name.to_owned(),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: None,
+            folders: folders.iter().map(|folder| folder.to_string()).collect(),
+            channel: ReleaseChannel::Stable,
+            pinned: false,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    fn installed(folder: &str, toc: &str) -> InstalledAddon {
+        InstalledAddon {
+            folder: folder.to_owned(),
+            toc: Toc::parse(folder, toc),
+        }
+    }
+
+    fn installed_addons() -> Vec<InstalledAddon> {
+        vec![
+            installed("ElvUI", ""),
+            installed("ElvUI_Options", "## DefaultState: disabled\n"),
+            installed("Details", ""),
+            installed("WeakAuras", ""),
+            installed("MyTweaks", "## DefaultState: disabled\n"),
+        ]
+    }
+
+    fn managed_addons() -> Vec<AddonData> {
+        vec![
+            addon("ElvUI", &["ElvUI", "ElvUI_Options"]),
+            addon("Details! Damage Meter", &["Details"]),
+        ]
+    }
+
+    #[test]
+    fn capture_splits_managed_and_unmanaged_addons() {
+        let addons_txt = AddonsTxt::parse("Details: disabled\nMyTweaks: enabled\n");
+
+        let profile = capture(
+            String::from("Raiding"),
+            &installed_addons(),
+            &managed_addons(),
+            &addons_txt,
+        );
+
+        // ElvUI_Options is disabled by default, but ElvUI enables the addon.
+        assert_eq!(profile.managed, ["ElvUI"]);
+        assert_eq!(profile.unmanaged, ["WeakAuras", "MyTweaks"]);
+    }
+
+    #[test]
+    fn apply_enables_exactly_the_profile() {
+        let profile = Profile {
+            name: String::from("Questing"),
+            managed: vec![String::from("ElvUI"), String::from("Removed Addon")],
+            unmanaged: vec![String::from("mytweaks"), String::from("Gone")],
+        };
+        let mut addons_txt = AddonsTxt::parse("Details: enabled\nWeakAuras: enabled\n");
+
+        let switch = apply(
+            &profile,
+            &installed_addons(),
+            &managed_addons(),
+            &mut addons_txt,
+        );
+
+        assert_eq!(switch.enabled, ["ElvUI", "ElvUI_Options", "MyTweaks"]);
+        assert_eq!(switch.disabled, ["Details", "WeakAuras"]);
+        assert_eq!(switch.missing, ["Removed Addon", "Gone"]);
+        assert_eq!(
+            addons_txt.to_string(),
+            "Details: disabled\n\
+             WeakAuras: disabled\n\
+             ElvUI: enabled\n\
+             ElvUI_Options: enabled\n\
+             MyTweaks: enabled\n"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{data::addons::AddonProvider, game::ReleaseType};
+
+/// The parsed contents of an addon's `.toc` file.
+#[derive(Clone, Serialize)]
+pub struct Toc {
+    pub folder: String,
+    /// `## Key: Value` metadata, keyed by the lowercased key since the client ignores case.
+    fields: HashMap<String, String>,
+    pub files: Vec<String>,
+}
+
+impl Toc {
+    pub fn parse(folder: &str, contents: &str) -> Self {
+        let mut fields = HashMap::new();
+        let mut files = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if let Some(metadata) = line.strip_prefix("##") {
+                if let Some((key, value)) = metadata.split_once(':') {
+                    fields
+                        .entry(key.trim().to_ascii_lowercase())
+                        .or_insert_with(|| value.trim().to_owned());
+                }
+            } else if !line.is_empty() && !line.starts_with('#') {
+                files.push(line.to_owned());
+            }
+        }
+
+        Self {
+            folder: folder.to_owned(),
+            fields,
+            files,
+        }
+    }
+
+    /// Reads the TOC the client would pick for `release_type` out of an addon folder.
+    pub fn read(addon_path: &Path, release_type: ReleaseType) -> Option<Self> {
+        let folder = addon_path.file_name()?.to_str()?;
+        let candidates = release_type
+            .get_toc_suffixes()
+            .iter()
+            .map(|suffix| format!("{folder}{suffix}.toc"))
+            .chain([format!("{folder}.toc")]);
+
+        for candidate in candidates {
+            if let Ok(contents) = fs::read_to_string(addon_path.join(candidate)) {
+                // Some authors save their TOC files with a byte order mark.
+                return Some(Self::parse(folder, contents.trim_start_matches('\u{feff}')));
+            }
+        }
+        None
+    }
+
+    /// Reads the first TOC in an addon folder regardless of flavor, for folders that
+    /// [`Toc::read`] rejects but whose metadata is still useful.
+    pub fn read_any(addon_path: &Path) -> Option<Self> {
+        let folder = addon_path.file_name()?.to_str()?;
+        let mut toc_paths: Vec<_> = fs::read_dir(addon_path)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toc"))
+            .collect();
+        toc_paths.sort();
+
+        let contents = fs::read_to_string(toc_paths.first()?).ok()?;
+        Some(Self::parse(folder, contents.trim_start_matches('\u{feff}')))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .get(&key.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Splits a comma separated field (e.g. `## Dependencies: A, B`) into its values.
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether the addon keeps settings in `WTF`, per account or per character.
+    pub fn has_saved_variables(&self) -> bool {
+        !self.get_list("SavedVariables").is_empty()
+            || !self.get_list("SavedVariablesPerCharacter").is_empty()
+    }
+
+    /// Every addon this one refuses to load without. The client accepts `Dependencies`,
+    /// `RequiredDeps` and any field starting with `Dep`.
+    pub fn get_required_dependencies(&self) -> Vec<String> {
+        let mut keys: Vec<&String> = self
+            .fields
+            .keys()
+            .filter(|key| key.starts_with("dep") || *key == "requireddeps")
+            .collect();
+        keys.sort();
+
+        let mut dependencies: Vec<String> = Vec::new();
+        for key in keys {
+            for dependency in self.get_list(key) {
+                if !dependencies
+                    .iter()
+                    .any(|existing| existing.eq_ignore_ascii_case(&dependency))
+                {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// The provider ids the author stored in the TOC's `X-*` metadata.
+    pub fn get_provider_ids(&self) -> Vec<(AddonProvider, String)> {
+        [
+            AddonProvider::CurseForge,
+            AddonProvider::Wago,
+            AddonProvider::WowInterface,
+        ]
+        .into_iter()
+        .filter_map(|provider| {
+            self.get(provider.get_toc_id_field())
+                .map(|id| (provider, id.to_owned()))
+        })
+        .collect()
+    }
+
+    /// The client versions listed in `## Interface`, which may hold several comma separated
+    /// values for addons that support more than one flavor.
+    pub fn get_interface_versions(&self) -> Vec<u32> {
+        self.get_list("Interface")
+            .iter()
+            .filter_map(|version| version.parse().ok())
+            .collect()
+    }
+
+    /// Whether the client will treat this addon as out of date. Addons without an
+    /// `## Interface` are always out of date.
+    pub fn is_out_of_date(&self, client_interface: u32) -> bool {
+        !self
+            .get_interface_versions()
+            .into_iter()
+            .any(|interface| interface >= client_interface)
+    }
+
+    /// Whether the author asked for the addon to be disabled until the user enables it.
+    pub fn is_disabled_by_default(&self) -> bool {
+        self.get("DefaultState")
+            .is_some_and(|state| state.eq_ignore_ascii_case("disabled"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Toc;
+
+    #[test]
+    fn interface_versions() {
+        let toc = Toc::parse(
+            "Details",
+            "## Interface: 110002, 40400 ,11504\n## Title: Details\n",
+        );
+
+        assert_eq!(toc.get_interface_versions(), [110002, 40400, 11504]);
+    }
+
+    #[test]
+    fn out_of_date() {
+        let toc = Toc::parse("Details", "## Interface: 110000\n");
+        assert!(!toc.is_out_of_date(110000));
+        assert!(toc.is_out_of_date(110002));
+
+        // Any listed version new enough for the client is enough.
+        let toc = Toc::parse("Details", "## Interface: 11504, 110002\n");
+        assert!(!toc.is_out_of_date(110002));
+        assert!(!toc.is_out_of_date(11504));
+        assert!(toc.is_out_of_date(110005));
+    }
+
+    #[test]
+    fn without_an_interface() {
+        assert!(Toc::parse("Details", "## Title: Details\n").is_out_of_date(110002));
+        assert!(Toc::parse("Details", "## Interface: soon\n").is_out_of_date(110002));
+    }
+}
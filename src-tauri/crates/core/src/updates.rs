@@ -0,0 +1,237 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+    data::{
+        addons::{AddonData, AddonProvider},
+        LocalSaveData,
+    },
+    game::{self, ReleaseType},
+    inventory::{self, InstalledAddon},
+    AppState,
+};
+
+/// A managed addon that should be checked against its provider for a newer version.
+#[derive(Clone, Serialize)]
+pub struct UpdateCandidate {
+    pub name: String,
+    pub provider: AddonProvider,
+    pub project_id: Option<String>,
+    pub version_id: String,
+    /// Whether any of the addon's folders is out of date for the current client.
+    pub out_of_date: bool,
+}
+
+/// A change of client build between two launches of WAM.
+#[derive(Clone, Serialize)]
+pub struct DetectedPatch {
+    pub release_type: ReleaseType,
+    pub previous: String,
+    pub current: String,
+    /// The addons the frontend should check for updates, out of date ones first.
+    pub addons_to_check: Vec<UpdateCandidate>,
+    /// Why the new build couldn't be recorded. The same patch is detected again on the next
+    /// launch when this is set.
+    pub save_error: Option<String>,
+}
+
+/// Orders the addons of `managed` that may be updated for an update check, putting those with
+/// an out of date folder first. Pinned addons stay on their version, so they are left out.
+fn order_addons_to_check(
+    managed: &[AddonData],
+    installed: &[InstalledAddon],
+    client_interface: Option<u32>,
+) -> Vec<UpdateCandidate> {
+    let is_out_of_date = |addon: &AddonData| {
+        client_interface.is_some_and(|client_interface| {
+            installed.iter().any(|installed| {
+                addon.claims_folder(&installed.folder)
+                    && installed.toc.is_out_of_date(client_interface)
+            })
+        })
+    };
+
+    let mut candidates: Vec<UpdateCandidate> = managed
+        .iter()
+        .filter(|addon| !addon.pinned)
+        .map(|addon| UpdateCandidate {
+            name: addon.name.clone(),
+            provider: addon.provider,
+            project_id: addon.project_id.clone(),
+            version_id: addon.version_id.clone(),
+            out_of_date: is_out_of_date(addon),
+        })
+        .collect();
+    // Stable, so addons keep their list order within each group.
+    candidates.sort_by_key(|candidate| !candidate.out_of_date);
+    candidates
+}
+
+/// The managed addons the frontend should check against their providers, in the order to
+/// check them. WAM doesn't query the providers itself.
+pub fn list_addons_to_check(state: &AppState, release_type: ReleaseType) -> Vec<UpdateCandidate> {
+    let (addons_path, client_interface) = match state.config.lock() {
+        Ok(config) => (
+            game::get_addons_path(&config, release_type),
+            game::read_game_version(&config, release_type)
+                .and_then(|version| game::get_interface_version(&version)),
+        ),
+        Err(_) => return Vec::new(),
+    };
+    let installed = addons_path
+        .map(|addons_path| inventory::scan(&addons_path, release_type))
+        .unwrap_or_default();
+
+    match state.addons.lock() {
+        Ok(addons) => {
+            order_addons_to_check(addons.get_list(release_type), &installed, client_interface)
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Compares each flavor's client build with the one seen on the last launch and records it.
+/// When a build changed, the frontend is told through the `patch-detected` event, along with
+/// the addons it should check for updates.
+pub fn detect_patches(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+
+    let mut detected = Vec::new();
+    for release_type in ReleaseType::ALL {
+        let version = match state.config.lock() {
+            Ok(config) => game::read_game_version(&config, release_type),
+            Err(_) => None,
+        };
+        let Some(version) = version else {
+            continue;
+        };
+
+        let (previous, save_error) = match state.persistent_state.lock() {
+            Ok(mut persistent_state) => {
+                let previous = persistent_state.update_build(release_type, &version);
+                let save_error = persistent_state.save().err().map(|error| error.to_string());
+                (previous, save_error)
+            }
+            Err(_) => (None, None),
+        };
+        if let Some(previous) = previous {
+            detected.push(DetectedPatch {
+                release_type,
+                previous,
+                current: version,
+                addons_to_check: list_addons_to_check(&state, release_type),
+                save_error,
+            });
+        }
+    }
+
+    if let Ok(mut detected_patches) = state.detected_patches.lock() {
+        detected_patches.extend(detected.iter().cloned());
+    }
+    for patch in detected {
+        let _ = app_handle.emit_all("patch-detected", patch);
+    }
+}
+
+/// The patches found at launch, for a frontend that wasn't listening yet when they were emitted.
+#[tauri::command]
+pub fn get_detected_patches(state: State<AppState>) -> Vec<DetectedPatch> {
+    match state.detected_patches.lock() {
+        Ok(detected_patches) => detected_patches.clone(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[tauri::command]
+pub fn get_addons_to_check(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Vec<UpdateCandidate> {
+    list_addons_to_check(&state, release_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::addons::{AddonData, AddonProvider, ReleaseChannel},
+        inventory::InstalledAddon,
+        toc::Toc,
+    };
+
+    use super::order_addons_to_check;
+
+    fn addon(name: &str, pinned: bool) -> AddonData {
+        AddonData {
+            name: name.to_owned(),
+            version_id: String::from("1"),
+            version_name: None,
+            provider: AddonProvider::CurseForge,
+            project_id: None,
+            folders: vec![name.to_owned()],
+            channel: ReleaseChannel::Stable,
+            pinned,
+            settings_snapshots: Vec::new(),
+        }
+    }
+
+    fn installed(folder: &str, interface: u32) -> InstalledAddon {
+        InstalledAddon {
+            folder: folder.to_owned(),
+            toc: Toc::parse(folder, &format!("## Interface: {interface}\n")),
+        }
+    }
+
+    #[test]
+    fn out_of_date_addons_come_first() {
+        let managed = [
+            addon("Current", false),
+            addon("Old", false),
+            addon("AlsoCurrent", false),
+            addon("AlsoOld", false),
+        ];
+        let installed = [
+            installed("Current", 110002),
+            installed("Old", 100207),
+            installed("AlsoCurrent", 110002),
+            installed("AlsoOld", 110000),
+        ];
+
+        let candidates = order_addons_to_check(&managed, &installed, Some(110002));
+
+        let order: Vec<(&str, bool)> = candidates
+            .iter()
+            .map(|candidate| (candidate.name.as_str(), candidate.out_of_date))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("Old", true),
+                ("AlsoOld", true),
+                ("Current", false),
+                ("AlsoCurrent", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn pinned_addons_are_left_out() {
+        let managed = [addon("Pinned", true), addon("Free", false)];
+        let installed = [installed("Pinned", 100207), installed("Free", 110002)];
+
+        let candidates = order_addons_to_check(&managed, &installed, Some(110002));
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].name, "Free");
+    }
+
+    #[test]
+    fn unknown_client_keeps_the_list_order() {
+        let managed = [addon("First", false), addon("Second", false)];
+        let installed = [installed("Second", 100207)];
+
+        let candidates = order_addons_to_check(&managed, &installed, None);
+
+        assert_eq!(candidates[0].name, "First");
+        assert!(candidates.iter().all(|candidate| !candidate.out_of_date));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{game::ReleaseType, AppState};
+
+use super::{get_configured_wtf_path, WtfTarget};
+
+pub const SAVED_VARIABLES_FOLDER: &str = "SavedVariables";
+
+/// An account folder inside `WTF/Account`.
+#[derive(Clone, Serialize)]
+pub struct WtfAccount {
+    pub name: String,
+    pub path: PathBuf,
+    /// Account-wide `SavedVariables`, which may not exist yet.
+    pub saved_variables_path: PathBuf,
+    pub realms: Vec<WtfRealm>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct WtfRealm {
+    pub name: String,
+    pub characters: Vec<WtfCharacter>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct WtfCharacter {
+    pub name: String,
+    pub path: PathBuf,
+    pub saved_variables_path: PathBuf,
+    /// When the game last wrote any of the character's files, in seconds since the Unix epoch.
+    /// The game writes them on logout, so this is roughly when the character was last played.
+    pub last_modified: Option<u64>,
+}
+
+impl WtfAccount {
+    pub fn get_target(&self) -> WtfTarget {
+        WtfTarget {
+            account: self.name.clone(),
+            realm: None,
+            character: None,
+        }
+    }
+
+    /// The account itself first, followed by each of its characters.
+    pub fn get_targets(&self) -> Vec<WtfTarget> {
+        let mut targets = vec![self.get_target()];
+        for realm in &self.realms {
+            for character in &realm.characters {
+                targets.push(WtfTarget {
+                    account: self.name.clone(),
+                    realm: Some(realm.name.clone()),
+                    character: Some(character.name.clone()),
+                });
+            }
+        }
+        targets
+    }
+}
+
+/// Walks `WTF/Account` into accounts, realms and characters, sorted by name.
+pub fn read_accounts(wtf_path: &Path) -> Vec<WtfAccount> {
+    list_accounts(wtf_path)
+        .into_iter()
+        .map(|account| {
+            let path = wtf_path.join("Account").join(&account);
+            let realms = list_realms(wtf_path, &account)
+                .into_iter()
+                .map(|realm| WtfRealm {
+                    characters: list_characters(wtf_path, &account, &realm)
+                        .into_iter()
+                        .map(|character| read_character(&path.join(&realm), character))
+                        .collect(),
+                    name: realm,
+                })
+                .collect();
+
+            WtfAccount {
+                saved_variables_path: path.join(SAVED_VARIABLES_FOLDER),
+                name: account,
+                path,
+                realms,
+            }
+        })
+        .collect()
+}
+
+fn read_character(realm_path: &Path, name: String) -> WtfCharacter {
+    let path = realm_path.join(&name);
+    let saved_variables_path = path.join(SAVED_VARIABLES_FOLDER);
+    let last_modified = [&path, &saved_variables_path]
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .max();
+
+    WtfCharacter {
+        name,
+        path,
+        saved_variables_path,
+        last_modified,
+    }
+}
+
+/// The names of the folders inside `dir`, sorted. A missing folder has none.
+fn list_dirs(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort_by_key(|name| name.to_ascii_lowercase());
+    names
+}
+
+pub fn list_accounts(wtf_path: &Path) -> Vec<String> {
+    list_dirs(&wtf_path.join("Account"))
+}
+
+pub fn list_realms(wtf_path: &Path, account: &str) -> Vec<String> {
+    let mut realms = list_dirs(&wtf_path.join("Account").join(account));
+    // The account's own SavedVariables folder sits next to its realms.
+    realms.retain(|realm| !realm.eq_ignore_ascii_case(SAVED_VARIABLES_FOLDER));
+    realms
+}
+
+pub fn list_characters(wtf_path: &Path, account: &str, realm: &str) -> Vec<String> {
+    list_dirs(&wtf_path.join("Account").join(account).join(realm))
+}
+
+#[tauri::command]
+pub fn get_wtf_accounts(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Result<Vec<WtfAccount>, String> {
+    Ok(read_accounts(&get_configured_wtf_path(
+        &state,
+        release_type,
+    )?))
+}
+
+#[tauri::command]
+pub fn list_wtf_accounts(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Result<Vec<String>, String> {
+    Ok(list_accounts(&get_configured_wtf_path(
+        &state,
+        release_type,
+    )?))
+}
+
+#[tauri::command]
+pub fn list_wtf_realms(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    account: String,
+) -> Result<Vec<String>, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    super::check_name(&account)?;
+    Ok(list_realms(&wtf_path, &account))
+}
+
+#[tauri::command]
+pub fn list_wtf_characters(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    account: String,
+    realm: String,
+) -> Result<Vec<String>, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    super::check_name(&account)?;
+    super::check_name(&realm)?;
+    Ok(list_characters(&wtf_path, &account, &realm))
+}
+
+/// Every account, and every character of every account.
+pub fn list_targets(wtf_path: &Path) -> Vec<WtfTarget> {
+    read_accounts(wtf_path)
+        .iter()
+        .flat_map(WtfAccount::get_targets)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{list_realms, list_targets, read_accounts};
+
+    fn create_wtf() -> tempfile::TempDir {
+        let wtf = tempfile::tempdir().unwrap();
+        let account = wtf.path().join("Account");
+        for dir in [
+            "ACCOUNT/SavedVariables",
+            "ACCOUNT/Silvermoon/Zed/SavedVariables",
+            "ACCOUNT/Silvermoon/anna",
+            "ACCOUNT/Argent Dawn/Bob",
+            "SECOND",
+        ] {
+            fs::create_dir_all(account.join(dir)).unwrap();
+        }
+        fs::write(account.join("ACCOUNT/bindings-cache.wtf"), "").unwrap();
+        fs::write(
+            account.join("ACCOUNT/Silvermoon/Zed/SavedVariables/ElvUI.lua"),
+            "ElvDB = {}",
+        )
+        .unwrap();
+        wtf
+    }
+
+    #[test]
+    fn accounts_realms_and_characters() {
+        let wtf = create_wtf();
+
+        let accounts = read_accounts(wtf.path());
+
+        let names: Vec<&str> = accounts
+            .iter()
+            .map(|account| account.name.as_str())
+            .collect();
+        assert_eq!(names, ["ACCOUNT", "SECOND"]);
+        let realms: Vec<(&str, Vec<&str>)> = accounts[0]
+            .realms
+            .iter()
+            .map(|realm| {
+                (
+                    realm.name.as_str(),
+                    realm
+                        .characters
+                        .iter()
+                        .map(|character| character.name.as_str())
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            realms,
+            [
+                ("Argent Dawn", vec!["Bob"]),
+                ("Silvermoon", vec!["anna", "Zed"])
+            ]
+        );
+        assert_eq!(
+            accounts[0].saved_variables_path,
+            wtf.path().join("Account/ACCOUNT/SavedVariables")
+        );
+        assert!(accounts[1].realms.is_empty());
+    }
+
+    #[test]
+    fn saved_variables_is_not_a_realm() {
+        let wtf = create_wtf();
+        fs::create_dir_all(wtf.path().join("Account/SECOND/savedvariables")).unwrap();
+
+        assert_eq!(
+            list_realms(wtf.path(), "ACCOUNT"),
+            ["Argent Dawn", "Silvermoon"]
+        );
+        assert!(list_realms(wtf.path(), "SECOND").is_empty());
+    }
+
+    #[test]
+    fn last_played_comes_from_the_character_files() {
+        let wtf = create_wtf();
+
+        let accounts = read_accounts(wtf.path());
+
+        let silvermoon = &accounts[0].realms[1];
+        assert!(silvermoon.characters[1].last_modified.is_some());
+        assert_eq!(silvermoon.characters[0].last_modified, None);
+    }
+
+    #[test]
+    fn targets_list_accounts_before_their_characters() {
+        let wtf = create_wtf();
+
+        let targets: Vec<String> = list_targets(wtf.path())
+            .iter()
+            .map(|target| {
+                [
+                    Some(target.account.as_str()),
+                    target.realm.as_deref(),
+                    target.character.as_deref(),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("/")
+            })
+            .collect();
+
+        assert_eq!(
+            targets,
+            [
+                "ACCOUNT",
+                "ACCOUNT/Argent Dawn/Bob",
+                "ACCOUNT/Silvermoon/anna",
+                "ACCOUNT/Silvermoon/Zed",
+                "SECOND",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_folders_have_no_accounts() {
+        let wtf = tempfile::tempdir().unwrap();
+
+        assert!(read_accounts(&wtf.path().join("WTF")).is_empty());
+    }
+}
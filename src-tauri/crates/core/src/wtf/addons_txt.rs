@@ -0,0 +1,306 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    data,
+    game::{self, ReleaseType},
+    inventory::{self, InstalledAddon},
+    operations::{self, Guarded, Operation},
+    AppState,
+};
+
+use super::{accounts, get_configured_wtf_path, WtfTarget};
+
+pub const FILE_NAME: &str = "AddOns.txt";
+
+/// The game's record of which addons are enabled, one `<Folder>: enabled` or
+/// `<Folder>: disabled` per line. Folders that aren't listed use their TOC's `DefaultState`.
+/// Every line is kept as it was read unless its state is changed, like in `ConfigWtf`.
+#[derive(Clone, Default)]
+pub struct AddonsTxt {
+    lines: Vec<AddonsTxtLine>,
+    /// Whether the file was read with Windows line endings.
+    crlf: bool,
+}
+
+#[derive(Clone)]
+enum AddonsTxtLine {
+    State {
+        folder: String,
+        enabled: bool,
+        /// The line as it was read, until the state is changed.
+        original: Option<String>,
+    },
+    Other(String),
+}
+
+/// Whether an installed addon loads for a target.
+#[derive(Serialize)]
+pub struct AddonEnablement {
+    pub folder: String,
+    pub title: Option<String>,
+    pub enabled: bool,
+    /// Whether `AddOns.txt` mentions the addon, or `enabled` comes from its `DefaultState`.
+    pub listed: bool,
+}
+
+pub fn get_path(wtf_path: &Path, target: &WtfTarget) -> Result<PathBuf, String> {
+    Ok(target.get_dir(wtf_path)?.join(FILE_NAME))
+}
+
+impl AddonsTxt {
+    pub fn parse(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .map(|line| {
+                // Folder names may contain colons, the state never does.
+                let parsed = line.trim().rsplit_once(':').and_then(|(folder, state)| {
+                    let enabled = match state.trim() {
+                        "enabled" => true,
+                        "disabled" => false,
+                        _ => return None,
+                    };
+                    Some(AddonsTxtLine::State {
+                        folder: folder.trim().to_owned(),
+                        enabled,
+                        original: Some(line.to_owned()),
+                    })
+                });
+                parsed.unwrap_or_else(|| AddonsTxtLine::Other(line.to_owned()))
+            })
+            .collect();
+        Self {
+            lines,
+            crlf: contents.contains("\r\n"),
+        }
+    }
+
+    /// Reads the file at `path`. A missing file is the same as an empty one.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        data::replace_atomically(path, self.to_string().as_bytes())
+    }
+
+    /// Whether `folder` is listed as enabled, or `None` if it isn't listed.
+    pub fn is_enabled(&self, folder: &str) -> Option<bool> {
+        self.lines.iter().find_map(|line| match line {
+            AddonsTxtLine::State {
+                folder: line_folder,
+                enabled,
+                ..
+            } if line_folder.eq_ignore_ascii_case(folder) => Some(*enabled),
+            _ => None,
+        })
+    }
+
+    /// Changes the first line of `folder`, or adds one at the end if there isn't any.
+    pub fn set_enabled(&mut self, folder: &str, enabled: bool) {
+        for line in &mut self.lines {
+            if let AddonsTxtLine::State {
+                folder: line_folder,
+                enabled: line_enabled,
+                original,
+            } = line
+            {
+                if line_folder.eq_ignore_ascii_case(folder) {
+                    if *line_enabled != enabled {
+                        *line_enabled = enabled;
+                        *original = None;
+                    }
+                    return;
+                }
+            }
+        }
+        self.lines.push(AddonsTxtLine::State {
+            folder: folder.to_owned(),
+            enabled,
+            original: None,
+        });
+    }
+}
+
+impl std::fmt::Display for AddonsTxt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line_ending = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            match line {
+                AddonsTxtLine::State {
+                    original: Some(line),
+                    ..
+                }
+                | AddonsTxtLine::Other(line) => write!(f, "{line}{line_ending}")?,
+                AddonsTxtLine::State {
+                    folder, enabled, ..
+                } => {
+                    let state = if *enabled { "enabled" } else { "disabled" };
+                    write!(f, "{folder}: {state}{line_ending}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves whether each installed addon is enabled by `addons_txt`.
+pub fn get_enablement(
+    installed: &[InstalledAddon],
+    addons_txt: &AddonsTxt,
+) -> Vec<AddonEnablement> {
+    installed
+        .iter()
+        .map(|addon| {
+            let listed = addons_txt.is_enabled(&addon.folder);
+            AddonEnablement {
+                folder: addon.folder.clone(),
+                title: addon.toc.get("Title").map(str::to_owned),
+                enabled: listed.unwrap_or(!addon.toc.is_disabled_by_default()),
+                listed: listed.is_some(),
+            }
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_addon_enablement(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+) -> Result<Vec<AddonEnablement>, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let addons_path = match state.config.lock() {
+        Ok(config) => game::get_addons_path(&config, release_type),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no game path is set"))?;
+
+    let addons_txt =
+        AddonsTxt::read(&get_path(&wtf_path, &target)?).map_err(|error| error.to_string())?;
+    Ok(get_enablement(
+        &inventory::scan(&addons_path, release_type),
+        &addons_txt,
+    ))
+}
+
+/// Enables or disables `folders` for `target`, or for every account and character when no
+/// target is given. Returns the files that were written.
+pub fn set_enabled_for(
+    state: &AppState,
+    release_type: ReleaseType,
+    target: Option<&WtfTarget>,
+    folders: &[String],
+    enabled: bool,
+) -> Result<Vec<String>, String> {
+    let wtf_path = get_configured_wtf_path(state, release_type)?;
+    let targets = match target {
+        Some(target) => vec![target.clone()],
+        None => accounts::list_targets(&wtf_path),
+    };
+
+    let mut written = Vec::new();
+    for target in &targets {
+        let path = get_path(&wtf_path, target)?;
+        let mut addons_txt = AddonsTxt::read(&path).map_err(|error| error.to_string())?;
+        for folder in folders {
+            addons_txt.set_enabled(folder, enabled);
+        }
+        addons_txt.write(&path).map_err(|error| error.to_string())?;
+        written.push(path.to_string_lossy().into_owned());
+    }
+    Ok(written)
+}
+
+#[tauri::command]
+pub fn set_addons_enabled(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: Option<WtfTarget>,
+    folders: Vec<String>,
+    enabled: bool,
+) -> Result<Guarded<Vec<String>>, String> {
+    if operations::queue_if_running(&state, || Operation::SetAddonsEnabled {
+        release_type,
+        target: target.clone(),
+        folders: folders.clone(),
+        enabled,
+    })? {
+        return Ok(Guarded::Queued);
+    }
+    set_enabled_for(&state, release_type, target.as_ref(), &folders, enabled).map(Guarded::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AddonsTxt;
+
+    const ADDONS_TXT: &str = "ElvUI: enabled\n\
+                              ElvUI_Options: disabled\n\
+                              \n\
+                              Addon: With: Colons : enabled\n\
+                              Broken: maybe\n\
+                              not an entry\n";
+
+    #[test]
+    fn unchanged_file_is_written_back_as_it_was() {
+        let addons_txt = AddonsTxt::parse(ADDONS_TXT);
+
+        assert_eq!(addons_txt.to_string(), ADDONS_TXT);
+    }
+
+    #[test]
+    fn states() {
+        let addons_txt = AddonsTxt::parse(ADDONS_TXT);
+
+        assert_eq!(addons_txt.is_enabled("elvui"), Some(true));
+        assert_eq!(addons_txt.is_enabled("ElvUI_Options"), Some(false));
+        assert_eq!(addons_txt.is_enabled("Addon: With: Colons"), Some(true));
+        assert_eq!(addons_txt.is_enabled("Broken"), None);
+        assert_eq!(addons_txt.is_enabled("Details"), None);
+    }
+
+    #[test]
+    fn set_enabled_changes_only_its_line() {
+        let mut addons_txt = AddonsTxt::parse(ADDONS_TXT);
+
+        addons_txt.set_enabled("elvui_options", true);
+        addons_txt.set_enabled("ElvUI", true);
+        addons_txt.set_enabled("Details", false);
+
+        assert_eq!(
+            addons_txt.to_string(),
+            "ElvUI: enabled\n\
+             ElvUI_Options: enabled\n\
+             \n\
+             Addon: With: Colons : enabled\n\
+             Broken: maybe\n\
+             not an entry\n\
+             Details: disabled\n"
+        );
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        let mut addons_txt = AddonsTxt::parse("ElvUI: enabled\r\nDetails: enabled\r\n");
+
+        addons_txt.set_enabled("Details", false);
+
+        assert_eq!(
+            addons_txt.to_string(),
+            "ElvUI: enabled\r\nDetails: disabled\r\n"
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{data, game::ReleaseType, AppState};
+
+use super::{
+    accounts, ensure_game_closed, get_configured_wtf_path,
+    snapshots::{self, SnapshotInfo},
+    WtfTarget, BINDINGS_FILE, MACROS_FILE,
+};
+
+/// The contents of `bindings-cache.wtf`.
+#[derive(Default, Serialize)]
+pub struct Bindings {
+    /// The `BINDINGMODE` line, which says whether these are account or character bindings.
+    pub mode: Option<String>,
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+pub struct Binding {
+    /// e.g. `SHIFT-1`.
+    pub key: String,
+    /// e.g. `ACTIONBUTTON1` or `SPELL Fireball`.
+    pub action: String,
+}
+
+/// A macro from `macros-cache.txt`.
+#[derive(Clone, PartialEq, Serialize)]
+pub struct Macro {
+    pub id: String,
+    pub name: String,
+    /// An icon file id or name.
+    pub icon: String,
+    pub body: String,
+}
+
+#[derive(Serialize)]
+pub struct BindingChange {
+    pub key: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MacroChange {
+    pub id: String,
+    pub name: String,
+    pub before: Option<Macro>,
+    pub after: Option<Macro>,
+}
+
+#[derive(Default, Serialize)]
+pub struct BindingsDiff {
+    pub bindings: Vec<BindingChange>,
+    pub macros: Vec<MacroChange>,
+}
+
+/// Parses `bind <KEY> <ACTION>` lines. Lines the game doesn't write are skipped.
+pub fn parse_bindings(contents: &str) -> Bindings {
+    let mut bindings = Bindings::default();
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(mode) = line.strip_prefix("BINDINGMODE ") {
+            bindings.mode = Some(mode.trim().to_owned());
+        } else if let Some(rest) = line.strip_prefix("bind ") {
+            if let Some((key, action)) = rest.trim().split_once(' ') {
+                bindings.bindings.push(Binding {
+                    key: key.to_owned(),
+                    action: action.trim().to_owned(),
+                });
+            }
+        }
+    }
+    bindings
+}
+
+/// Parses macros written as `MACRO <id> "<name>" <icon>`, followed by the body and `END`.
+pub fn parse_macros(contents: &str) -> Vec<Macro> {
+    let mut macros = Vec::new();
+    let mut lines = contents.lines();
+
+    while let Some(line) = lines.next() {
+        let Some(header) = line.trim().strip_prefix("MACRO ") else {
+            continue;
+        };
+        let Some((id, rest)) = header.split_once(' ') else {
+            continue;
+        };
+        let Some((name, icon)) = rest
+            .strip_prefix('"')
+            .and_then(|rest| rest.rsplit_once('"'))
+        else {
+            continue;
+        };
+
+        let mut body = Vec::new();
+        for line in lines.by_ref() {
+            if line.trim_end() == "END" {
+                break;
+            }
+            body.push(line);
+        }
+        macros.push(Macro {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            icon: icon.trim().to_owned(),
+            body: body.join("\n"),
+        });
+    }
+    macros
+}
+
+/// A macro before and after a change, keyed by `get_macro_key` in `diff`.
+type MacroVersions<'a> = (Option<&'a Macro>, Option<&'a Macro>);
+
+/// Macros are told apart by their id, so a renamed macro is a change rather than one macro
+/// removed and another added. Several macros can share a name, so only a macro without an id
+/// is matched by its name.
+fn get_macro_key(macro_: &Macro) -> (&str, &str) {
+    if macro_.id.is_empty() {
+        ("", &macro_.name)
+    } else {
+        (&macro_.id, "")
+    }
+}
+
+/// Compares two versions of the bindings and macros of a target.
+pub fn diff(
+    bindings_before: &Bindings,
+    bindings_after: &Bindings,
+    macros_before: &[Macro],
+    macros_after: &[Macro],
+) -> BindingsDiff {
+    let mut diff = BindingsDiff::default();
+
+    let mut keys: BTreeMap<&str, (Option<&str>, Option<&str>)> = BTreeMap::new();
+    for binding in &bindings_before.bindings {
+        keys.entry(&binding.key).or_default().0 = Some(&binding.action);
+    }
+    for binding in &bindings_after.bindings {
+        keys.entry(&binding.key).or_default().1 = Some(&binding.action);
+    }
+    for (key, (before, after)) in keys {
+        if before != after {
+            diff.bindings.push(BindingChange {
+                key: key.to_owned(),
+                before: before.map(str::to_owned),
+                after: after.map(str::to_owned),
+            });
+        }
+    }
+
+    let mut macros: BTreeMap<(&str, &str), MacroVersions> = BTreeMap::new();
+    for macro_ in macros_before {
+        macros.entry(get_macro_key(macro_)).or_default().0 = Some(macro_);
+    }
+    for macro_ in macros_after {
+        macros.entry(get_macro_key(macro_)).or_default().1 = Some(macro_);
+    }
+    for (before, after) in macros.into_values() {
+        if before == after {
+            continue;
+        }
+        // The name it has now, or had before it was deleted.
+        if let Some(current) = after.or(before) {
+            diff.macros.push(MacroChange {
+                id: current.id.clone(),
+                name: current.name.clone(),
+                before: before.cloned(),
+                after: after.cloned(),
+            });
+        }
+    }
+    diff
+}
+
+/// The entry names of the bindings and macros files of `targets`.
+fn get_entry_names(wtf_path: &Path, targets: &[WtfTarget]) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    for target in targets {
+        let dir = target.get_dir(wtf_path)?;
+        for file_name in [BINDINGS_FILE, MACROS_FILE] {
+            if let Some(name) = snapshots::get_entry_name(wtf_path, &dir.join(file_name)) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Reads one of a target's files either from snapshot `id` or, without one, from disk.
+fn read_version(
+    wtf_path: &Path,
+    target: &WtfTarget,
+    file_name: &str,
+    id: Option<&str>,
+) -> Result<String, String> {
+    let path = target.get_dir(wtf_path)?.join(file_name);
+    let contents = match id {
+        Some(id) => match snapshots::get_entry_name(wtf_path, &path) {
+            Some(name) => snapshots::read_file(id, &name)?,
+            None => None,
+        },
+        None => fs::read(path).ok(),
+    };
+    Ok(String::from_utf8_lossy(&contents.unwrap_or_default()).into_owned())
+}
+
+#[tauri::command]
+pub fn get_bindings(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+) -> Result<Bindings, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    Ok(parse_bindings(&read_version(
+        &wtf_path,
+        &target,
+        BINDINGS_FILE,
+        None,
+    )?))
+}
+
+#[tauri::command]
+pub fn get_macros(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+) -> Result<Vec<Macro>, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    Ok(parse_macros(&read_version(
+        &wtf_path,
+        &target,
+        MACROS_FILE,
+        None,
+    )?))
+}
+
+/// Snapshots the bindings and macros of `target`, or of every account and character.
+#[tauri::command]
+pub fn backup_bindings(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: Option<WtfTarget>,
+    label: String,
+) -> Result<SnapshotInfo, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let targets = match target {
+        Some(target) => vec![target],
+        None => accounts::list_targets(&wtf_path),
+    };
+
+    let snapshot = snapshots::create_for_files(
+        &wtf_path,
+        release_type,
+        label,
+        get_entry_names(&wtf_path, &targets)?,
+    )?;
+    snapshots::apply_configured_retention_keeping(&state, &[&snapshot.id])?;
+    Ok(snapshot)
+}
+
+/// Compares the bindings and macros of `target` in snapshot `id` with snapshot `other`, or
+/// with the files on disk.
+#[tauri::command]
+pub fn diff_bindings(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+    id: String,
+    other: Option<String>,
+) -> Result<BindingsDiff, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let read = |file_name, id| read_version(&wtf_path, &target, file_name, id);
+
+    Ok(diff(
+        &parse_bindings(&read(BINDINGS_FILE, Some(id.as_str()))?),
+        &parse_bindings(&read(BINDINGS_FILE, other.as_deref())?),
+        &parse_macros(&read(MACROS_FILE, Some(id.as_str()))?),
+        &parse_macros(&read(MACROS_FILE, other.as_deref())?),
+    ))
+}
+
+/// Restores only the bindings and macros of `target` from snapshot `id`, which may be a full
+/// WTF snapshot. The current files are snapshotted first.
+#[tauri::command]
+pub fn restore_bindings(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+    id: String,
+) -> Result<SnapshotInfo, String> {
+    ensure_game_closed()?;
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let names = get_entry_names(&wtf_path, std::slice::from_ref(&target))?;
+
+    let mut restored = Vec::new();
+    for name in &names {
+        if let Some(contents) = snapshots::read_file(&id, name)? {
+            restored.push((wtf_path.join(name), contents));
+        }
+    }
+    if restored.is_empty() {
+        return Err(String::from(
+            "the snapshot has no bindings or macros for this character",
+        ));
+    }
+
+    let backup = snapshots::create_for_files(
+        &wtf_path,
+        release_type,
+        String::from("Before restoring keybindings and macros"),
+        names,
+    )?;
+    for (path, contents) in restored {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        data::replace_atomically(&path, &contents).map_err(|error| error.to_string())?;
+    }
+    snapshots::apply_configured_retention_keeping(&state, &[&backup.id])?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, parse_bindings, parse_macros};
+
+    const MACROS: &str = "MACRO 16777217 \"Heal\" INV_Misc_QuestionMark\n\
+                          #showtooltip\n\
+                          /cast Flash Heal\n\
+                          END\n\
+                          MACRO 16777218 \"Heal\" 135907\n\
+                          /cast [@focus] Flash Heal\n\
+                          END\n\
+                          MACRO 16777219 \"Mount \"Up\"\" 132261\n\
+                          END\n";
+
+    #[test]
+    fn bindings() {
+        let bindings = parse_bindings(
+            "BINDINGMODE 0\r\nbind SHIFT-1 ACTIONBUTTON1\r\nbind F SPELL Flash Heal\r\n\
+             unknown line\r\nbind BROKEN\r\n",
+        );
+
+        assert_eq!(bindings.mode.as_deref(), Some("0"));
+        let bindings: Vec<(&str, &str)> = bindings
+            .bindings
+            .iter()
+            .map(|binding| (binding.key.as_str(), binding.action.as_str()))
+            .collect();
+        assert_eq!(
+            bindings,
+            [("SHIFT-1", "ACTIONBUTTON1"), ("F", "SPELL Flash Heal")]
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let macros = parse_macros(MACROS);
+
+        assert_eq!(macros.len(), 3);
+        assert_eq!(macros[0].id, "16777217");
+        assert_eq!(macros[0].name, "Heal");
+        assert_eq!(macros[0].icon, "INV_Misc_QuestionMark");
+        assert_eq!(macros[0].body, "#showtooltip\n/cast Flash Heal");
+        assert_eq!(macros[1].icon, "135907");
+        assert_eq!(macros[2].name, "Mount \"Up\"");
+        assert_eq!(macros[2].body, "");
+    }
+
+    #[test]
+    fn unterminated_macro_keeps_its_body() {
+        let macros = parse_macros("MACRO 1 \"Open\" 1\n/dance\n");
+
+        assert_eq!(macros.len(), 1);
+        assert_eq!(macros[0].body, "/dance");
+    }
+
+    #[test]
+    fn diff_of_bindings() {
+        let before = parse_bindings("bind 1 ACTIONBUTTON1\nbind 2 ACTIONBUTTON2\nbind 3 JUMP\n");
+        let after = parse_bindings("bind 1 ACTIONBUTTON1\nbind 2 SITORSTAND\nbind 4 JUMP\n");
+
+        let changes = diff(&before, &after, &[], &[]).bindings;
+
+        let changes: Vec<(&str, Option<&str>, Option<&str>)> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.key.as_str(),
+                    change.before.as_deref(),
+                    change.after.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("2", Some("ACTIONBUTTON2"), Some("SITORSTAND")),
+                ("3", Some("JUMP"), None),
+                ("4", None, Some("JUMP")),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_keeps_macros_with_the_same_name_apart() {
+        let bindings = parse_bindings("");
+        let before = parse_macros(MACROS);
+        let mut after = parse_macros(MACROS);
+        after[1].body = String::from("/cast [@mouseover] Flash Heal");
+        after.remove(2);
+
+        let changes = diff(&bindings, &bindings, &before, &after).macros;
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].id, "16777218");
+        assert_eq!(changes[0].name, "Heal");
+        assert_eq!(
+            changes[0].after.as_ref().map(|macro_| macro_.body.as_str()),
+            Some("/cast [@mouseover] Flash Heal")
+        );
+        assert_eq!(changes[1].id, "16777219");
+        assert!(changes[1].after.is_none());
+    }
+
+    #[test]
+    fn diff_shows_a_renamed_macro_as_changed() {
+        let bindings = parse_bindings("");
+        let before = parse_macros(MACROS);
+        let mut after = parse_macros(MACROS);
+        after[0].name = String::from("Big Heal");
+
+        let changes = diff(&bindings, &bindings, &before, &after).macros;
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].id, "16777217");
+        assert_eq!(changes[0].name, "Big Heal");
+        assert_eq!(
+            changes[0]
+                .before
+                .as_ref()
+                .map(|macro_| macro_.name.as_str()),
+            Some("Heal")
+        );
+    }
+
+    #[test]
+    fn diff_matches_macros_without_an_id_by_name() {
+        let bindings = parse_bindings("");
+        let before = parse_macros("MACRO  \"Dance\" 1\n/dance\nEND\n");
+        let after = parse_macros("MACRO  \"Dance\" 1\n/dance\n/cheer\nEND\n");
+        assert_eq!(before[0].id, "");
+
+        let changes = diff(&bindings, &bindings, &before, &after).macros;
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name, "Dance");
+        assert!(changes[0].before.is_some() && changes[0].after.is_some());
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{game::ReleaseType, AppState};
+
+use super::{
+    accounts::SAVED_VARIABLES_FOLDER, addons_txt, ensure_game_closed, get_configured_wtf_path,
+    snapshots, WtfTarget, BINDINGS_FILE, MACROS_FILE,
+};
+
+/// Which of a character's or account's files to copy.
+#[derive(Clone, Copy, Deserialize)]
+pub struct CopyOptions {
+    pub saved_variables: bool,
+    pub addons_txt: bool,
+    pub bindings: bool,
+    pub macros: bool,
+}
+
+#[derive(Serialize)]
+pub struct PlannedCopy {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Whether a file at `destination` would be replaced.
+    pub overwrites: bool,
+}
+
+#[derive(Serialize)]
+pub struct CopyReport {
+    pub dry_run: bool,
+    pub files: Vec<PlannedCopy>,
+    /// The snapshot of the destination files that were replaced, if any were.
+    pub backup_snapshot: Option<String>,
+}
+
+/// Lists the files that copying `source` to `destination` would write. With `folders`, only
+/// the SavedVariables of those addon folders are copied.
+pub fn plan(
+    wtf_path: &Path,
+    source: &WtfTarget,
+    destination: &WtfTarget,
+    options: CopyOptions,
+    folders: Option<&[String]>,
+) -> Result<Vec<PlannedCopy>, String> {
+    if source.character.is_some() != destination.character.is_some() {
+        return Err(String::from(
+            "settings can only be copied from a character to a character, or from an account to an account",
+        ));
+    }
+    let source_dir = source.get_dir(wtf_path)?;
+    let destination_dir = destination.get_dir(wtf_path)?;
+    if source_dir == destination_dir {
+        return Err(String::from("the source and destination are the same"));
+    }
+
+    let mut file_names = Vec::new();
+    if options.addons_txt {
+        file_names.push(PathBuf::from(addons_txt::FILE_NAME));
+    }
+    if options.bindings {
+        file_names.push(PathBuf::from(BINDINGS_FILE));
+    }
+    if options.macros {
+        file_names.push(PathBuf::from(MACROS_FILE));
+    }
+    if options.saved_variables {
+        let entries = fs::read_dir(source_dir.join(SAVED_VARIABLES_FOLDER)).into_iter();
+        for entry in entries.flatten().flatten() {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            // The `.lua.bak` files are the game's own backups, and stay behind.
+            let Some(folder) = file_name.strip_suffix(".lua") else {
+                continue;
+            };
+            let selected = folders.map_or(true, |folders| {
+                folders
+                    .iter()
+                    .any(|selected| selected.eq_ignore_ascii_case(folder))
+            });
+            if selected {
+                file_names.push(Path::new(SAVED_VARIABLES_FOLDER).join(file_name));
+            }
+        }
+    }
+
+    Ok(file_names
+        .into_iter()
+        .map(|file_name| (source_dir.join(&file_name), destination_dir.join(file_name)))
+        .filter(|(source, _)| source.is_file())
+        .map(|(source, destination)| PlannedCopy {
+            overwrites: destination.exists(),
+            source,
+            destination,
+        })
+        .collect())
+}
+
+/// Copies a character's or account's settings to another one. Unless it is a dry run, the
+/// destination files that get replaced are snapshotted first.
+#[tauri::command]
+pub fn copy_wtf_settings(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    source: WtfTarget,
+    destination: WtfTarget,
+    options: CopyOptions,
+    folders: Option<Vec<String>>,
+    dry_run: bool,
+) -> Result<CopyReport, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let files = plan(
+        &wtf_path,
+        &source,
+        &destination,
+        options,
+        folders.as_deref(),
+    )?;
+    if dry_run {
+        return Ok(CopyReport {
+            dry_run,
+            files,
+            backup_snapshot: None,
+        });
+    }
+    ensure_game_closed()?;
+
+    let replaced: Vec<String> = files
+        .iter()
+        .filter(|file| file.overwrites)
+        .filter_map(|file| snapshots::get_entry_name(&wtf_path, &file.destination))
+        .collect();
+    let backup_snapshot = if replaced.is_empty() {
+        None
+    } else {
+        let snapshot = snapshots::create_for_files(
+            &wtf_path,
+            release_type,
+            format!(
+                "Before copying settings to {}",
+                snapshots::get_entry_name(&wtf_path, &destination.get_dir(&wtf_path)?)
+                    .unwrap_or_default()
+            ),
+            replaced,
+        )?;
+        Some(snapshot.id)
+    };
+
+    for file in &files {
+        if let Some(parent) = file.destination.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let contents = fs::read(&file.source).map_err(|error| error.to_string())?;
+        crate::data::replace_atomically(&file.destination, &contents)
+            .map_err(|error| error.to_string())?;
+    }
+    if let Some(backup_snapshot) = &backup_snapshot {
+        snapshots::apply_configured_retention_keeping(&state, &[backup_snapshot])?;
+    }
+
+    Ok(CopyReport {
+        dry_run,
+        files,
+        backup_snapshot,
+    })
+}
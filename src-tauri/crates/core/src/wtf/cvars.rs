@@ -0,0 +1,313 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    data,
+    game::ReleaseType,
+    operations::{self, Guarded, Operation},
+    AppState,
+};
+
+use super::{get_configured_wtf_path, WtfTarget};
+
+pub const CONFIG_FILE: &str = "Config.wtf";
+pub const ACCOUNT_CONFIG_FILE: &str = "config-cache.wtf";
+
+/// `Config.wtf` or an account's `config-cache.wtf`. Every line is kept as it was read unless
+/// its CVar is set, so writing the file back only changes those lines.
+#[derive(Default)]
+pub struct ConfigWtf {
+    lines: Vec<ConfigLine>,
+    /// Whether the file was read with Windows line endings.
+    crlf: bool,
+}
+
+enum ConfigLine {
+    Set {
+        name: String,
+        value: String,
+        /// The line as it was read, until the value is changed.
+        original: Option<String>,
+    },
+    Other(String),
+}
+
+#[derive(Serialize)]
+pub struct Cvar {
+    pub name: String,
+    pub value: String,
+}
+
+/// Checks that `SET <name> "<value>"` can be written as a single line the game reads back.
+fn validate(name: &str, value: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("'{name}' is not a valid CVar name"));
+    }
+    if value.contains(['"', '\n', '\r']) {
+        return Err(format!(
+            "the value of '{name}' can't contain quotes or newlines"
+        ));
+    }
+    Ok(())
+}
+
+/// `WTF/Config.wtf`, or `WTF/Account/<ACCOUNT>/config-cache.wtf` for an account.
+pub fn get_path(wtf_path: &Path, account: Option<&str>) -> Result<PathBuf, String> {
+    match account {
+        Some(account) => Ok(WtfTarget {
+            account: account.to_owned(),
+            realm: None,
+            character: None,
+        }
+        .get_dir(wtf_path)?
+        .join(ACCOUNT_CONFIG_FILE)),
+        None => Ok(wtf_path.join(CONFIG_FILE)),
+    }
+}
+
+impl ConfigWtf {
+    pub fn parse(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .map(|line| {
+                let parsed = line.trim().strip_prefix("SET ").and_then(|rest| {
+                    let (name, value) = rest.trim().split_once(' ')?;
+                    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+                    Some(ConfigLine::Set {
+                        name: name.to_owned(),
+                        value: value.to_owned(),
+                        original: Some(line.to_owned()),
+                    })
+                });
+                parsed.unwrap_or_else(|| ConfigLine::Other(line.to_owned()))
+            })
+            .collect();
+        Self {
+            lines,
+            crlf: contents.contains("\r\n"),
+        }
+    }
+
+    /// Reads the file at `path`. A missing file is the same as an empty one.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        data::replace_atomically(path, self.to_string().as_bytes())
+    }
+
+    /// Every CVar in the file. When one is set twice, the game uses the last value.
+    pub fn get_cvars(&self) -> Vec<Cvar> {
+        let mut cvars: BTreeMap<String, Cvar> = BTreeMap::new();
+        for line in &self.lines {
+            if let ConfigLine::Set { name, value, .. } = line {
+                cvars.insert(
+                    name.to_ascii_lowercase(),
+                    Cvar {
+                        name: name.clone(),
+                        value: value.clone(),
+                    },
+                );
+            }
+        }
+        cvars.into_values().collect()
+    }
+
+    /// Changes every `SET` line of `name`, or adds one at the end if there isn't any.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        validate(name, value)?;
+
+        let mut found = false;
+        for line in &mut self.lines {
+            if let ConfigLine::Set {
+                name: line_name,
+                value: line_value,
+                original,
+            } = line
+            {
+                if line_name.eq_ignore_ascii_case(name) {
+                    if line_value != value {
+                        *line_value = value.to_owned();
+                        *original = None;
+                    }
+                    found = true;
+                }
+            }
+        }
+        if !found {
+            self.lines.push(ConfigLine::Set {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                original: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigWtf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_ending = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            match line {
+                ConfigLine::Set {
+                    original: Some(line),
+                    ..
+                }
+                | ConfigLine::Other(line) => write!(f, "{line}{line_ending}")?,
+                ConfigLine::Set { name, value, .. } => {
+                    write!(f, "SET {name} \"{value}\"{line_ending}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the CVars of `Config.wtf`, or of an account's `config-cache.wtf`.
+#[tauri::command]
+pub fn get_cvars(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    account: Option<String>,
+) -> Result<Vec<Cvar>, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let config = ConfigWtf::read(&get_path(&wtf_path, account.as_deref())?)
+        .map_err(|error| error.to_string())?;
+    Ok(config.get_cvars())
+}
+
+/// Sets the given CVars, leaving the rest of the file alone.
+pub fn set_all(
+    state: &AppState,
+    release_type: ReleaseType,
+    account: Option<&str>,
+    cvars: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let wtf_path = get_configured_wtf_path(state, release_type)?;
+    let path = get_path(&wtf_path, account)?;
+
+    let mut config = ConfigWtf::read(&path).map_err(|error| error.to_string())?;
+    for (name, value) in cvars {
+        config.set(name, value)?;
+    }
+    config.write(&path).map_err(|error| error.to_string())
+}
+
+/// Sets the given CVars, e.g. `scriptErrors` to `1`, or queues them while the game is running.
+#[tauri::command]
+pub fn set_cvars(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    account: Option<String>,
+    cvars: BTreeMap<String, String>,
+) -> Result<Guarded<()>, String> {
+    // A queued change is only written once the game exits, too late to report a bad CVar.
+    for (name, value) in &cvars {
+        validate(name, value)?;
+    }
+    if operations::queue_if_running(&state, || Operation::SetCvars {
+        release_type,
+        account: account.clone(),
+        cvars: cvars.clone(),
+    })? {
+        return Ok(Guarded::Queued);
+    }
+    set_all(&state, release_type, account.as_deref(), &cvars).map(Guarded::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, ConfigWtf};
+
+    const CONFIG: &str = "# written by the game\n\
+                          SET portal \"EU\"\n\
+                          \n\
+                          SET   scriptErrors  \"0\"\n\
+                          SET gxWindow \"1\"\n\
+                          SET broken value\n\
+                          unknown line\n\
+                          SET SCRIPTERRORS \"0\"\n";
+
+    #[test]
+    fn unchanged_file_is_written_back_as_it_was() {
+        let config = ConfigWtf::parse(CONFIG);
+
+        assert_eq!(config.to_string(), CONFIG);
+    }
+
+    #[test]
+    fn cvars() {
+        let cvars = ConfigWtf::parse(CONFIG).get_cvars();
+
+        let cvars: Vec<(&str, &str)> = cvars
+            .iter()
+            .map(|cvar| (cvar.name.as_str(), cvar.value.as_str()))
+            .collect();
+        assert_eq!(
+            cvars,
+            [("gxWindow", "1"), ("portal", "EU"), ("SCRIPTERRORS", "0")]
+        );
+    }
+
+    #[test]
+    fn set_changes_only_its_lines() {
+        let mut config = ConfigWtf::parse(CONFIG);
+
+        config.set("scripterrors", "1").unwrap();
+        config.set("gxWindow", "1").unwrap();
+        config.set("maxFPS", "60").unwrap();
+
+        assert_eq!(
+            config.to_string(),
+            "# written by the game\n\
+             SET portal \"EU\"\n\
+             \n\
+             SET scriptErrors \"1\"\n\
+             SET gxWindow \"1\"\n\
+             SET broken value\n\
+             unknown line\n\
+             SET SCRIPTERRORS \"1\"\n\
+             SET maxFPS \"60\"\n"
+        );
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        let mut config = ConfigWtf::parse("SET portal \"EU\"\r\n\r\n");
+
+        config.set("portal", "US").unwrap();
+
+        assert_eq!(config.to_string(), "SET portal \"US\"\r\n\r\n");
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let mut config = ConfigWtf::default();
+
+        assert!(config.set("bad name", "1").is_err());
+        assert!(config.set("", "1").is_err());
+        assert!(config.set("portal", "\"EU").is_err());
+        assert!(config.set("portal", "EU\nSET other \"1\"").is_err());
+        assert_eq!(config.to_string(), "");
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate("scriptErrors", "1").is_ok());
+        assert!(validate("gxMaximize_2", "").is_ok());
+        assert!(validate("bad-name", "1").is_err());
+        assert!(validate("portal", "EU\r").is_err());
+    }
+}
@@ -0,0 +1,552 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// How deeply tables may be nested. Real SavedVariables stay far below this, and a file that
+/// doesn't would otherwise overflow the stack.
+const MAX_DEPTH: usize = 200;
+
+/// A value from the subset of Lua the game writes to SavedVariables files.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum LuaValue {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    /// Fields in the order they were written, so arrays keep their order.
+    Table(Vec<LuaField>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LuaField {
+    pub key: LuaValue,
+    pub value: LuaValue,
+}
+
+/// A top level assignment, e.g. `ElvDB = { ... }`.
+#[derive(Clone, Debug, Serialize)]
+pub struct LuaVariable {
+    pub name: String,
+    pub value: LuaValue,
+    /// How many bytes of the file the assignment takes up.
+    pub size: usize,
+}
+
+#[derive(Debug)]
+pub struct LuaError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl LuaValue {
+    /// The key as it would be shown in a path, e.g. `profiles` or `1`.
+    pub fn to_key_string(&self) -> String {
+        match self {
+            LuaValue::Nil => String::from("nil"),
+            LuaValue::Boolean(value) => value.to_string(),
+            LuaValue::Number(value) => value.to_string(),
+            LuaValue::String(value) => value.clone(),
+            LuaValue::Table(_) => String::from("{...}"),
+        }
+    }
+}
+
+/// Parses every top level assignment of a SavedVariables file.
+pub fn parse(contents: &str) -> Result<Vec<LuaVariable>, LuaError> {
+    let mut parser = Parser {
+        bytes: contents.as_bytes(),
+        position: 0,
+        depth: 0,
+    };
+    // Files saved by some editors start with a byte order mark.
+    if parser.bytes.starts_with(b"\xEF\xBB\xBF") {
+        parser.position = 3;
+    }
+
+    let mut variables = Vec::new();
+    loop {
+        parser.skip_whitespace()?;
+        if parser.peek().is_none() {
+            return Ok(variables);
+        }
+
+        let start = parser.position;
+        let name = parser.parse_name()?;
+        parser.skip_whitespace()?;
+        parser.expect(b'=')?;
+        let value = parser.parse_value()?;
+        variables.push(LuaVariable {
+            name,
+            value,
+            size: parser.position - start,
+        });
+        parser.skip_whitespace()?;
+        parser.eat(b';');
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// How many tables are open at `position`.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.position + offset).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), LuaError> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> LuaError {
+        LuaError {
+            line: self.bytes[..self.position.min(self.bytes.len())]
+                .iter()
+                .filter(|byte| **byte == b'\n')
+                .count()
+                + 1,
+            message: message.into(),
+        }
+    }
+
+    /// Skips whitespace and comments, including the `-- [1]` the game writes after array items.
+    fn skip_whitespace(&mut self) -> Result<(), LuaError> {
+        loop {
+            match self.peek() {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'-') if self.peek_at(1) == Some(b'-') => {
+                    self.position += 2;
+                    if let Some(level) = self.long_bracket_level() {
+                        self.read_long_bracket(level)?;
+                    } else {
+                        while !matches!(self.peek(), None | Some(b'\n')) {
+                            self.position += 1;
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// The level of a long bracket (`[[` is 0, `[==[` is 2) starting here, if there is one.
+    fn long_bracket_level(&self) -> Option<usize> {
+        if self.peek() != Some(b'[') {
+            return None;
+        }
+        let mut level = 0;
+        while self.peek_at(level + 1) == Some(b'=') {
+            level += 1;
+        }
+        (self.peek_at(level + 1) == Some(b'[')).then_some(level)
+    }
+
+    fn read_long_bracket(&mut self, level: usize) -> Result<Vec<u8>, LuaError> {
+        self.position += level + 2;
+        // A newline right after the opening bracket isn't part of the string.
+        self.eat(b'\r');
+        self.eat(b'\n');
+
+        let mut closing = vec![b']'];
+        closing.resize(level + 1, b'=');
+        closing.push(b']');
+
+        let start = self.position;
+        while self.position < self.bytes.len() {
+            if self.bytes[self.position..].starts_with(&closing) {
+                let contents = self.bytes[start..self.position].to_vec();
+                self.position += closing.len();
+                return Ok(contents);
+            }
+            self.position += 1;
+        }
+        Err(self.error("unfinished long string or comment"))
+    }
+
+    fn parse_name(&mut self) -> Result<String, LuaError> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        {
+            self.position += 1;
+        }
+        if start == self.position || self.bytes[start].is_ascii_digit() {
+            return Err(self.error("expected a name"));
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.position]).into_owned())
+    }
+
+    fn parse_value(&mut self) -> Result<LuaValue, LuaError> {
+        self.skip_whitespace()?;
+        match self.peek() {
+            Some(b'{') => self.parse_table(),
+            Some(quote @ (b'"' | b'\'')) => self.parse_string(quote).map(LuaValue::String),
+            Some(b'[') => match self.long_bracket_level() {
+                Some(level) => Ok(LuaValue::String(
+                    String::from_utf8_lossy(&self.read_long_bracket(level)?).into_owned(),
+                )),
+                None => Err(self.error("unexpected '['")),
+            },
+            Some(byte) if byte == b'-' || byte == b'.' || byte.is_ascii_digit() => {
+                self.parse_number().map(LuaValue::Number)
+            }
+            Some(_) => match self.parse_name()?.as_str() {
+                "nil" => Ok(LuaValue::Nil),
+                "true" => Ok(LuaValue::Boolean(true)),
+                "false" => Ok(LuaValue::Boolean(false)),
+                "inf" => Ok(LuaValue::Number(f64::INFINITY)),
+                "nan" => Ok(LuaValue::Number(f64::NAN)),
+                name => Err(self.error(format!("unexpected '{name}'"))),
+            },
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn parse_table(&mut self) -> Result<LuaValue, LuaError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("tables are nested too deeply"));
+        }
+        self.expect(b'{')?;
+        self.depth += 1;
+        let mut fields = Vec::new();
+        let mut next_index = 1.0;
+
+        loop {
+            self.skip_whitespace()?;
+            if self.eat(b'}') {
+                self.depth -= 1;
+                return Ok(LuaValue::Table(fields));
+            }
+
+            let field = if self.peek() == Some(b'[') && self.long_bracket_level().is_none() {
+                self.position += 1;
+                let key = self.parse_value()?;
+                self.skip_whitespace()?;
+                self.expect(b']')?;
+                self.skip_whitespace()?;
+                self.expect(b'=')?;
+                LuaField {
+                    key,
+                    value: self.parse_value()?,
+                }
+            } else if self.is_name_assignment() {
+                let key = LuaValue::String(self.parse_name()?);
+                self.skip_whitespace()?;
+                self.expect(b'=')?;
+                LuaField {
+                    key,
+                    value: self.parse_value()?,
+                }
+            } else {
+                let field = LuaField {
+                    key: LuaValue::Number(next_index),
+                    value: self.parse_value()?,
+                };
+                next_index += 1.0;
+                field
+            };
+            fields.push(field);
+
+            self.skip_whitespace()?;
+            if !self.eat(b',') && !self.eat(b';') && self.peek() != Some(b'}') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    /// Whether a `name = value` field starts here, rather than a value like `true`.
+    fn is_name_assignment(&self) -> bool {
+        if !self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphabetic() || byte == b'_')
+        {
+            return false;
+        }
+        let mut offset = 0;
+        while self
+            .peek_at(offset)
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        {
+            offset += 1;
+        }
+        while self
+            .peek_at(offset)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            offset += 1;
+        }
+        self.peek_at(offset) == Some(b'=') && self.peek_at(offset + 1) != Some(b'=')
+    }
+
+    fn parse_string(&mut self, quote: u8) -> Result<String, LuaError> {
+        self.position += 1;
+        let mut bytes = Vec::new();
+
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("unfinished string"));
+            };
+            self.position += 1;
+            match byte {
+                _ if byte == quote => break,
+                b'\n' => return Err(self.error("unfinished string")),
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return Err(self.error("unfinished string"));
+                    };
+                    self.position += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'a' => bytes.push(0x07),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0C),
+                        b'v' => bytes.push(0x0B),
+                        b'\n' => bytes.push(b'\n'),
+                        b'0'..=b'9' => {
+                            // `\ddd` is a byte written as up to three decimal digits.
+                            let mut value = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'9') => {
+                                        value = value * 10 + u32::from(digit - b'0');
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            let byte = u8::try_from(value)
+                                .map_err(|_| self.error("escape sequence is too large"))?;
+                            bytes.push(byte);
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn parse_number(&mut self) -> Result<f64, LuaError> {
+        let start = self.position;
+        let negative = self.eat(b'-');
+
+        if self.peek() == Some(b'0') && matches!(self.peek_at(1), Some(b'x' | b'X')) {
+            self.position += 2;
+            let digits_start = self.position;
+            while self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+                self.position += 1;
+            }
+            let digits = std::str::from_utf8(&self.bytes[digits_start..self.position])
+                .map_err(|_| self.error("invalid number"))?;
+            let value =
+                u64::from_str_radix(digits, 16).map_err(|_| self.error("invalid number"))? as f64;
+            return Ok(if negative { -value } else { value });
+        }
+
+        while let Some(byte) = self.peek() {
+            let is_exponent_sign = matches!(byte, b'+' | b'-')
+                && matches!(self.bytes.get(self.position - 1), Some(b'e' | b'E'));
+            if byte.is_ascii_alphanumeric() || byte == b'.' || is_exponent_sign {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text = std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| self.error("invalid number"))?;
+        match text {
+            "-inf" => Ok(f64::NEG_INFINITY),
+            "-nan" => Ok(f64::NAN),
+            _ => text
+                .parse()
+                .map_err(|_| self.error(format!("invalid number '{text}'"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, LuaField, LuaValue, MAX_DEPTH};
+
+    fn string(value: &str) -> LuaValue {
+        LuaValue::String(value.to_owned())
+    }
+
+    fn field(key: LuaValue, value: LuaValue) -> LuaField {
+        LuaField { key, value }
+    }
+
+    /// Parses a file with a single variable and returns its value.
+    fn parse_value(contents: &str) -> LuaValue {
+        let mut variables = parse(contents).unwrap_or_else(|error| panic!("{error}"));
+        assert_eq!(variables.len(), 1);
+        variables.remove(0).value
+    }
+
+    #[test]
+    fn variables() {
+        let contents = "\u{feff}A = 1\nB = \"two\"\n";
+        let variables = parse(contents).unwrap_or_else(|error| panic!("{error}"));
+
+        assert_eq!(variables.len(), 2);
+        assert_eq!(variables[0].name, "A");
+        assert_eq!(variables[0].value, LuaValue::Number(1.0));
+        assert_eq!(variables[0].size, "A = 1".len());
+        assert_eq!(variables[1].value, string("two"));
+    }
+
+    #[test]
+    fn nested_tables() {
+        let value = parse_value(
+            "ElvDB = {\n\t[\"profiles\"] = {\n\t\t[\"Default\"] = {\n\t\t\t[\"scale\"] = 0.75,\n\t\t},\n\t},\n}",
+        );
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![field(
+                string("profiles"),
+                LuaValue::Table(vec![field(
+                    string("Default"),
+                    LuaValue::Table(vec![field(string("scale"), LuaValue::Number(0.75))]),
+                )]),
+            )])
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let value =
+            parse_value("T = { [\"key\"] = 1, [2] = \"two\", name = true, \"first\", [-1.5] = 3 }");
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![
+                field(string("key"), LuaValue::Number(1.0)),
+                field(LuaValue::Number(2.0), string("two")),
+                field(string("name"), LuaValue::Boolean(true)),
+                field(LuaValue::Number(1.0), string("first")),
+                field(LuaValue::Number(-1.5), LuaValue::Number(3.0)),
+            ])
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let value = parse_value(r#"S = "a\"b\\c\n\t\65\066\0671|cffff0000\124r""#);
+
+        assert_eq!(value, string("a\"b\\c\n\tAB\u{43}1|cffff0000|r"));
+    }
+
+    #[test]
+    fn escape_out_of_range() {
+        assert!(parse(r#"S = "\256""#).is_err());
+    }
+
+    #[test]
+    fn booleans_and_nil() {
+        let value = parse_value("T = { true, false, nil, [\"off\"] = false }");
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![
+                field(LuaValue::Number(1.0), LuaValue::Boolean(true)),
+                field(LuaValue::Number(2.0), LuaValue::Boolean(false)),
+                field(LuaValue::Number(3.0), LuaValue::Nil),
+                field(string("off"), LuaValue::Boolean(false)),
+            ])
+        );
+    }
+
+    #[test]
+    fn array_comments() {
+        let value = parse_value(
+            "T = {\n\t\"a\", -- [1]\n\t\"b\", -- [2]\n}\n-- trailing comment\n--[[ block\ncomment ]]",
+        );
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![
+                field(LuaValue::Number(1.0), string("a")),
+                field(LuaValue::Number(2.0), string("b")),
+            ])
+        );
+    }
+
+    #[test]
+    fn long_strings() {
+        let value = parse_value("T = { [[\nfirst\nline]], [==[has ]] and \"quotes\"]==] }");
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![
+                field(LuaValue::Number(1.0), string("first\nline")),
+                field(LuaValue::Number(2.0), string("has ]] and \"quotes\"")),
+            ])
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let value = parse_value("T = { 0x1F, -2, 1e3, 1.5E-2, inf, -inf }");
+
+        assert_eq!(
+            value,
+            LuaValue::Table(vec![
+                field(LuaValue::Number(1.0), LuaValue::Number(31.0)),
+                field(LuaValue::Number(2.0), LuaValue::Number(-2.0)),
+                field(LuaValue::Number(3.0), LuaValue::Number(1000.0)),
+                field(LuaValue::Number(4.0), LuaValue::Number(0.015)),
+                field(LuaValue::Number(5.0), LuaValue::Number(f64::INFINITY)),
+                field(LuaValue::Number(6.0), LuaValue::Number(f64::NEG_INFINITY)),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_have_a_line() {
+        let error = parse("A = 1\nB = {\n\t[\"x\"] 2,\n}").err().unwrap();
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let at_limit = format!("T = {}{}", "{".repeat(MAX_DEPTH), "}".repeat(MAX_DEPTH));
+        assert!(parse(&at_limit).is_ok());
+
+        let too_deep = format!("T = {}", "{".repeat(100_000));
+        let error = parse(&too_deep).err().unwrap();
+        assert!(error.message.contains("too deeply"), "{error}");
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::config::AppConfig,
+    game::{self, ReleaseType},
+    AppState,
+};
+
+pub mod accounts;
+pub mod addons_txt;
+pub mod bindings;
+pub mod copy;
+pub mod cvars;
+pub mod lua;
+pub mod saved_variables;
+pub mod snapshots;
+
+pub const BINDINGS_FILE: &str = "bindings-cache.wtf";
+pub const MACROS_FILE: &str = "macros-cache.txt";
+
+/// Returns the flavor's `WTF` folder if a game path is configured.
+pub fn get_wtf_path(config: &AppConfig, release_type: ReleaseType) -> Option<PathBuf> {
+    game::get_flavor_path(config, release_type).map(|path| path.join("WTF"))
+}
+
+/// Like `get_wtf_path`, for commands that only have the app state.
+pub fn get_configured_wtf_path(
+    state: &AppState,
+    release_type: ReleaseType,
+) -> Result<PathBuf, String> {
+    match state.config.lock() {
+        Ok(config) => get_wtf_path(&config, release_type),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no game path is set"))
+}
+
+/// The game rewrites its WTF files when it exits, so anything WAM wrote meanwhile would be lost.
+pub fn ensure_game_closed() -> Result<(), String> {
+    if game::is_game_running() {
+        return Err(String::from(
+            "World of Warcraft is running, close it before changing its files",
+        ));
+    }
+    Ok(())
+}
+
+/// Whose settings a file in `WTF/Account` belongs to. Without a realm and character the
+/// account-wide file is meant.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WtfTarget {
+    pub account: String,
+    pub realm: Option<String>,
+    pub character: Option<String>,
+}
+
+impl WtfTarget {
+    /// The folder holding the target's files, e.g. `WTF/Account/<ACCOUNT>/<Realm>/<Character>`.
+    pub fn get_dir(&self, wtf_path: &Path) -> Result<PathBuf, String> {
+        let mut dir = wtf_path.join("Account");
+        dir.push(check_name(&self.account)?);
+
+        match (&self.realm, &self.character) {
+            (Some(realm), Some(character)) => {
+                dir.push(check_name(realm)?);
+                dir.push(check_name(character)?);
+            }
+            (None, None) => {}
+            _ => return Err(String::from("a character needs both a realm and a name")),
+        }
+        Ok(dir)
+    }
+}
+
+/// Names come from the frontend, so they mustn't be able to point outside of `WTF`.
+fn check_name(name: &str) -> Result<&str, String> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(format!("'{name}' is not a valid folder name")),
+    }
+}
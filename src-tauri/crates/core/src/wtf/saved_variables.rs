@@ -0,0 +1,428 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    game::{self, ReleaseType},
+    inventory, AppState,
+};
+
+use super::{
+    accounts::{self, SAVED_VARIABLES_FOLDER},
+    ensure_game_closed, get_configured_wtf_path,
+    lua::{self, LuaValue, LuaVariable},
+    snapshots, WtfTarget,
+};
+
+/// A key found by `search`, with the keys leading to it.
+#[derive(Serialize)]
+pub struct KeyMatch {
+    /// The top level variable first, e.g. `["ElvDB", "profiles", "Default"]`.
+    pub path: Vec<String>,
+    /// The matched value, unless it is a table.
+    pub value: Option<LuaValue>,
+    /// How many fields the matched table has, if it is one.
+    pub fields: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct VariableSize {
+    pub name: String,
+    pub size: usize,
+}
+
+/// A SavedVariables file, for the bloat report.
+#[derive(Serialize)]
+pub struct SavedVariablesFile {
+    pub target: WtfTarget,
+    /// The addon folder the file belongs to.
+    pub folder: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Whether this is the game's `.lua.bak` copy from the previous logout.
+    pub is_backup: bool,
+    /// The file's top level variables, largest first.
+    pub variables: Vec<VariableSize>,
+    /// Why the variables couldn't be read, if they couldn't.
+    pub error: Option<String>,
+    /// Whether the addon the file belongs to isn't installed anymore.
+    pub orphaned: bool,
+}
+
+#[derive(Serialize)]
+pub struct BloatReport {
+    pub total_size: u64,
+    /// Every SavedVariables file, largest first.
+    pub files: Vec<SavedVariablesFile>,
+}
+
+#[derive(Serialize)]
+pub struct OrphanCleanup {
+    /// The snapshot the removed files were backed up to.
+    pub snapshot_id: Option<String>,
+    pub removed: Vec<PathBuf>,
+}
+
+/// `<target>/SavedVariables/<folder>.lua`.
+pub fn get_path(wtf_path: &Path, target: &WtfTarget, folder: &str) -> Result<PathBuf, String> {
+    Ok(target
+        .get_dir(wtf_path)?
+        .join(SAVED_VARIABLES_FOLDER)
+        .join(format!("{}.lua", super::check_name(folder)?)))
+}
+
+pub fn read(path: &Path) -> Result<Vec<LuaVariable>, String> {
+    // SavedVariables are usually UTF-8, but addons can store any bytes in strings.
+    let contents = fs::read(path).map_err(|error| error.to_string())?;
+    lua::parse(&String::from_utf8_lossy(&contents)).map_err(|error| error.to_string())
+}
+
+/// Finds every key containing `query`, ignoring case.
+pub fn search(variables: &[LuaVariable], query: &str) -> Vec<KeyMatch> {
+    let query = query.to_lowercase();
+    let mut matches = Vec::new();
+    for variable in variables {
+        let mut path = vec![variable.name.clone()];
+        if variable.name.to_lowercase().contains(&query) {
+            matches.push(to_match(path.clone(), &variable.value));
+        }
+        search_value(&variable.value, &query, &mut path, &mut matches);
+    }
+    matches
+}
+
+fn search_value(
+    value: &LuaValue,
+    query: &str,
+    path: &mut Vec<String>,
+    matches: &mut Vec<KeyMatch>,
+) {
+    let LuaValue::Table(fields) = value else {
+        return;
+    };
+    for field in fields {
+        path.push(field.key.to_key_string());
+        if matches!(field.key, LuaValue::String(_))
+            && field.key.to_key_string().to_lowercase().contains(query)
+        {
+            matches.push(to_match(path.clone(), &field.value));
+        }
+        search_value(&field.value, query, path, matches);
+        path.pop();
+    }
+}
+
+fn to_match(path: Vec<String>, value: &LuaValue) -> KeyMatch {
+    match value {
+        LuaValue::Table(fields) => KeyMatch {
+            path,
+            value: None,
+            fields: Some(fields.len()),
+        },
+        _ => KeyMatch {
+            path,
+            value: Some(value.clone()),
+            fields: None,
+        },
+    }
+}
+
+/// The size of each top level variable, largest first.
+pub fn get_sizes(variables: &[LuaVariable]) -> Vec<VariableSize> {
+    let mut sizes: Vec<VariableSize> = variables
+        .iter()
+        .map(|variable| VariableSize {
+            name: variable.name.clone(),
+            size: variable.size,
+        })
+        .collect();
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.size));
+    sizes
+}
+
+/// A SavedVariables file found in `WTF`, before it is read.
+struct ListedFile {
+    target: WtfTarget,
+    folder: String,
+    path: PathBuf,
+    is_backup: bool,
+}
+
+/// Lists the SavedVariables files of every account and character, including the game's
+/// `.lua.bak` copies.
+fn list_files(wtf_path: &Path) -> Vec<ListedFile> {
+    let mut files = Vec::new();
+    for account in accounts::read_accounts(wtf_path) {
+        for target in account.get_targets() {
+            let Ok(dir) = target.get_dir(wtf_path) else {
+                continue;
+            };
+            let Ok(entries) = fs::read_dir(dir.join(SAVED_VARIABLES_FOLDER)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(file_name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let (folder, is_backup) = match file_name.strip_suffix(".lua.bak") {
+                    Some(folder) => (folder, true),
+                    None => match file_name.strip_suffix(".lua") {
+                        Some(folder) => (folder, false),
+                        None => continue,
+                    },
+                };
+                files.push(ListedFile {
+                    target: target.clone(),
+                    folder: folder.to_owned(),
+                    path: entry.path(),
+                    is_backup,
+                });
+            }
+        }
+    }
+    files
+}
+
+/// Whether the file of `folder` belongs to none of `installed_names`. Blizzard's own addons
+/// never are orphaned, since they ship inside the client.
+fn is_orphaned(folder: &str, installed_names: &[String]) -> bool {
+    !folder.starts_with("Blizzard_")
+        && !installed_names
+            .iter()
+            .any(|installed| installed.eq_ignore_ascii_case(folder))
+}
+
+/// Lists the SavedVariables files of every account and character. Files of addons that aren't
+/// among `installed_names` are marked as orphaned.
+pub fn get_bloat_report(wtf_path: &Path, installed_names: &[String]) -> BloatReport {
+    let mut files: Vec<SavedVariablesFile> = list_files(wtf_path)
+        .into_iter()
+        .map(|file| {
+            let (variables, error) = match read(&file.path) {
+                Ok(variables) => (get_sizes(&variables), None),
+                Err(error) => (Vec::new(), Some(error)),
+            };
+            SavedVariablesFile {
+                size: fs::metadata(&file.path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0),
+                orphaned: is_orphaned(&file.folder, installed_names),
+                target: file.target,
+                folder: file.folder,
+                path: file.path,
+                is_backup: file.is_backup,
+                variables,
+                error,
+            }
+        })
+        .collect();
+
+    files.sort_by_key(|file| std::cmp::Reverse(file.size));
+    BloatReport {
+        total_size: files.iter().map(|file| file.size).sum(),
+        files,
+    }
+}
+
+/// Finds the files of addons that aren't among `installed_names`, or only those of `folders`.
+/// Only the file names are looked at, so broken or huge files are found just as fast.
+fn find_orphans(
+    wtf_path: &Path,
+    installed_names: &[String],
+    folders: Option<&[String]>,
+) -> Vec<ListedFile> {
+    list_files(wtf_path)
+        .into_iter()
+        .filter(|file| is_orphaned(&file.folder, installed_names))
+        .filter(|file| match folders {
+            Some(folders) => folders
+                .iter()
+                .any(|folder| folder.eq_ignore_ascii_case(&file.folder)),
+            None => true,
+        })
+        .collect()
+}
+
+/// The names a SavedVariables file may have without being orphaned: every installed addon
+/// folder, and the variables their TOCs declare.
+fn get_installed_names(state: &AppState, release_type: ReleaseType) -> Result<Vec<String>, String> {
+    let addons_path = match state.config.lock() {
+        Ok(config) => game::get_addons_path(&config, release_type),
+        Err(error) => return Err(error.to_string()),
+    }
+    .ok_or_else(|| String::from("no game path is set"))?;
+    // `inventory::scan` treats a folder it can't read as empty, which would make every file
+    // look orphaned.
+    fs::read_dir(&addons_path)
+        .map_err(|error| format!("{} could not be read: {error}", addons_path.display()))?;
+
+    let mut names = Vec::new();
+    for addon in inventory::scan(&addons_path, release_type) {
+        names.extend(addon.toc.get_list("SavedVariables"));
+        names.extend(addon.toc.get_list("SavedVariablesPerCharacter"));
+        names.push(addon.folder);
+    }
+    Ok(names)
+}
+
+fn read_target(
+    state: &AppState,
+    release_type: ReleaseType,
+    target: &WtfTarget,
+    folder: &str,
+) -> Result<Vec<LuaVariable>, String> {
+    let wtf_path = get_configured_wtf_path(state, release_type)?;
+    read(&get_path(&wtf_path, target, folder)?)
+}
+
+/// Parses the SavedVariables of the addon in `folder` for `target`.
+#[tauri::command]
+pub fn browse_saved_variables(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+    folder: String,
+) -> Result<Vec<LuaVariable>, String> {
+    read_target(&state, release_type, &target, &folder)
+}
+
+#[tauri::command]
+pub fn search_saved_variables(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+    folder: String,
+    query: String,
+) -> Result<Vec<KeyMatch>, String> {
+    Ok(search(
+        &read_target(&state, release_type, &target, &folder)?,
+        &query,
+    ))
+}
+
+#[tauri::command]
+pub fn get_saved_variables_sizes(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    target: WtfTarget,
+    folder: String,
+) -> Result<Vec<VariableSize>, String> {
+    Ok(get_sizes(&read_target(
+        &state,
+        release_type,
+        &target,
+        &folder,
+    )?))
+}
+
+#[tauri::command]
+pub fn get_saved_variables_report(
+    state: State<AppState>,
+    release_type: ReleaseType,
+) -> Result<BloatReport, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let installed_names = get_installed_names(&state, release_type)?;
+    Ok(get_bloat_report(&wtf_path, &installed_names))
+}
+
+/// Removes the SavedVariables of addons that aren't installed anymore, or only those of
+/// `folders`. The files are snapshotted first so they can be restored.
+#[tauri::command]
+pub fn remove_orphaned_saved_variables(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    folders: Option<Vec<String>>,
+) -> Result<OrphanCleanup, String> {
+    ensure_game_closed()?;
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let installed_names = get_installed_names(&state, release_type)?;
+    if installed_names.is_empty() {
+        return Err(String::from(
+            "no addons were found, so nothing is removed in case the AddOns folder is wrong",
+        ));
+    }
+
+    let orphans = find_orphans(&wtf_path, &installed_names, folders.as_deref());
+    if orphans.is_empty() {
+        return Ok(OrphanCleanup {
+            snapshot_id: None,
+            removed: Vec::new(),
+        });
+    }
+
+    let mut orphaned_folders: Vec<String> =
+        orphans.iter().map(|file| file.folder.clone()).collect();
+    orphaned_folders.sort();
+    orphaned_folders.dedup();
+    let snapshot = snapshots::create(
+        &wtf_path,
+        release_type,
+        String::from("Before removing orphaned SavedVariables"),
+        Some(orphaned_folders),
+    )?;
+
+    let mut removed = Vec::new();
+    for file in orphans {
+        fs::remove_file(&file.path).map_err(|error| error.to_string())?;
+        removed.push(file.path);
+    }
+    snapshots::apply_configured_retention_keeping(&state, &[&snapshot.id])?;
+    Ok(OrphanCleanup {
+        snapshot_id: Some(snapshot.id),
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::find_orphans;
+
+    #[test]
+    fn orphans_are_found_by_name() {
+        let wtf = tempfile::tempdir().unwrap();
+        let wtf_path = wtf.path();
+        let account = wtf_path.join("Account").join("ACCOUNT");
+        let character = account.join("Realm").join("Character");
+        for dir in [&account, &character] {
+            fs::create_dir_all(dir.join("SavedVariables")).unwrap();
+        }
+        for (dir, name) in [
+            (&account, "ElvUI.lua"),
+            (&account, "Removed.lua"),
+            (&account, "Removed.lua.bak"),
+            (&account, "Blizzard_Console.lua"),
+            (&account, "Notes.txt"),
+            (&character, "elvui.lua"),
+            (&character, "Gone.lua"),
+        ] {
+            // Not Lua at all, to show that the files aren't parsed.
+            fs::write(dir.join("SavedVariables").join(name), "{{{").unwrap();
+        }
+        let installed = [String::from("ElvUI")];
+
+        let mut orphans: Vec<String> = find_orphans(wtf_path, &installed, None)
+            .into_iter()
+            .map(|file| {
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        orphans.sort();
+        assert_eq!(orphans, ["Gone.lua", "Removed.lua", "Removed.lua.bak"]);
+
+        let only = [String::from("gone")];
+        let orphans = find_orphans(wtf_path, &installed, Some(&only));
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].folder, "Gone");
+        assert_eq!(orphans[0].target.character.as_deref(), Some("Character"));
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use zip::{result::ZipError, write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    data::{
+        config::SnapshotSettings,
+        location::{self, DataKind},
+    },
+    game::ReleaseType,
+    AppState,
+};
+
+use super::{accounts::SAVED_VARIABLES_FOLDER, ensure_game_closed, get_configured_wtf_path};
+
+/// Stored inside every snapshot archive, next to the copied files.
+const MANIFEST_NAME: &str = "wam-snapshot.toml";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub label: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    pub release_type: ReleaseType,
+    /// The addon folders whose SavedVariables were copied, or `None` for the whole WTF folder.
+    pub folders: Option<Vec<String>>,
+    /// Exact files relative to `WTF`, copied instead of a selection by folder.
+    #[serde(default)]
+    pub files: Option<Vec<String>>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SnapshotInfo {
+    /// The archive's file name without `.zip`.
+    pub id: String,
+    #[serde(flatten)]
+    pub manifest: SnapshotManifest,
+    /// The archive's size in bytes.
+    pub size: u64,
+}
+
+/// Differences between two sets of WTF files, by path relative to `WTF`.
+#[derive(Default, Serialize)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+fn get_snapshots_dir() -> Result<PathBuf, String> {
+    let dir = location::get_or_create_dir(DataKind::State)
+        .ok_or_else(|| String::from("the data directory could not be found"))?
+        .join("snapshots");
+    fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+    Ok(dir)
+}
+
+fn get_snapshot_path(id: &str) -> Result<PathBuf, String> {
+    super::check_name(id)?;
+    let path = get_snapshots_dir()?.join(format!("{id}.zip"));
+    if !path.is_file() {
+        return Err(format!("there is no snapshot called '{id}'"));
+    }
+    Ok(path)
+}
+
+/// Whether `relative_path` is a SavedVariables file of one of `folders`, e.g.
+/// `Account/NAME/SavedVariables/ElvUI.lua` or its `.lua.bak` for `ElvUI`.
+fn is_saved_variables_of(relative_path: &str, folders: &[String]) -> bool {
+    let mut components = relative_path.rsplit('/');
+    let (Some(file_name), Some(parent)) = (components.next(), components.next()) else {
+        return false;
+    };
+    if !parent.eq_ignore_ascii_case(SAVED_VARIABLES_FOLDER) {
+        return false;
+    }
+    let Some(stem) = file_name
+        .strip_suffix(".lua")
+        .or_else(|| file_name.strip_suffix(".lua.bak"))
+    else {
+        return false;
+    };
+    folders
+        .iter()
+        .any(|folder| folder.eq_ignore_ascii_case(stem))
+}
+
+/// The name a snapshot stores `path` under: relative to `wtf_path`, with `/` separators.
+pub fn get_entry_name(wtf_path: &Path, path: &Path) -> Option<String> {
+    let relative_path = path.strip_prefix(wtf_path).ok()?;
+    let components: Vec<String> = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(components.join("/"))
+}
+
+/// Every file below `dir`, keyed by its entry name.
+fn collect_files(wtf_path: &Path, dir: &Path, files: &mut BTreeMap<String, PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(wtf_path, &path, files);
+        } else if let Some(name) = get_entry_name(wtf_path, &path) {
+            files.insert(name, path);
+        }
+    }
+}
+
+/// The files a snapshot of exactly `selected`, of the SavedVariables of `folders` or of
+/// everything contains, out of the ones that exist right now.
+fn select_files(
+    wtf_path: &Path,
+    folders: Option<&[String]>,
+    selected: Option<&[String]>,
+) -> BTreeMap<String, PathBuf> {
+    let mut files = BTreeMap::new();
+    collect_files(wtf_path, wtf_path, &mut files);
+    if let Some(selected) = selected {
+        files.retain(|name, _| selected.contains(name));
+    } else if let Some(folders) = folders {
+        files.retain(|name, _| is_saved_variables_of(name, folders));
+    }
+    files
+}
+
+/// Zips the SavedVariables of `folders`, or the whole of `wtf_path`, into the snapshot folder.
+pub fn create(
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    label: String,
+    folders: Option<Vec<String>>,
+) -> Result<SnapshotInfo, String> {
+    create_with(wtf_path, release_type, label, folders, None)
+}
+
+/// Zips exactly `files`, given relative to `WTF` with `/` separators. Files that don't exist
+/// are skipped.
+pub fn create_for_files(
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    label: String,
+    files: Vec<String>,
+) -> Result<SnapshotInfo, String> {
+    create_with(wtf_path, release_type, label, None, Some(files))
+}
+
+fn create_with(
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    label: String,
+    folders: Option<Vec<String>>,
+    files: Option<Vec<String>>,
+) -> Result<SnapshotInfo, String> {
+    let snapshots_dir = get_snapshots_dir()?;
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let mut id = created.to_string();
+    let mut suffix = 1;
+    while snapshots_dir.join(format!("{id}.zip")).exists() {
+        suffix += 1;
+        id = format!("{created}-{suffix}");
+    }
+
+    let manifest = SnapshotManifest {
+        label,
+        created,
+        release_type,
+        folders,
+        files,
+    };
+    let path = snapshots_dir.join(format!("{id}.zip"));
+    let files = select_files(
+        wtf_path,
+        manifest.folders.as_deref(),
+        manifest.files.as_deref(),
+    );
+    if let Err(error) = write_archive(&path, &manifest, &files) {
+        let _ = fs::remove_file(&path);
+        return Err(error);
+    }
+
+    Ok(SnapshotInfo {
+        id,
+        size: fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0),
+        manifest,
+    })
+}
+
+/// Snapshots the SavedVariables of `folders`, unless none of them have any yet.
+pub fn create_for_folders(
+    wtf_path: &Path,
+    release_type: ReleaseType,
+    label: String,
+    folders: Vec<String>,
+) -> Result<Option<SnapshotInfo>, String> {
+    if select_files(wtf_path, Some(&folders), None).is_empty() {
+        return Ok(None);
+    }
+    create(wtf_path, release_type, label, Some(folders)).map(Some)
+}
+
+fn write_archive(
+    path: &Path,
+    manifest: &SnapshotManifest,
+    files: &BTreeMap<String, PathBuf>,
+) -> Result<(), String> {
+    let mut writer = ZipWriter::new(File::create(path).map_err(|error| error.to_string())?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let manifest = toml::to_string_pretty(manifest).map_err(|error| error.to_string())?;
+    writer
+        .start_file(MANIFEST_NAME, options)
+        .map_err(|error| error.to_string())?;
+    writer
+        .write_all(manifest.as_bytes())
+        .map_err(|error| error.to_string())?;
+
+    for (name, file_path) in files {
+        writer
+            .start_file(name.as_str(), options)
+            .map_err(|error| error.to_string())?;
+        io::copy(
+            &mut File::open(file_path).map_err(|error| error.to_string())?,
+            &mut writer,
+        )
+        .map_err(|error| error.to_string())?;
+    }
+    writer.finish().map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<File>, String> {
+    ZipArchive::new(File::open(path).map_err(|error| error.to_string())?)
+        .map_err(|error| error.to_string())
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<SnapshotManifest, String> {
+    let mut contents = String::new();
+    archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|error| error.to_string())?
+        .read_to_string(&mut contents)
+        .map_err(|error| error.to_string())?;
+    toml::from_str(&contents).map_err(|error| error.to_string())
+}
+
+/// The contents of the file stored as `name` in snapshot `id`, if the snapshot has it.
+pub fn read_file(id: &str, name: &str) -> Result<Option<Vec<u8>>, String> {
+    let mut archive = open_archive(&get_snapshot_path(id)?)?;
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(error) => return Err(error.to_string()),
+    };
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .map_err(|error| error.to_string())?;
+    Ok(Some(contents))
+}
+
+/// Every snapshot, newest first. Archives that can't be read are skipped.
+pub fn list() -> Result<Vec<SnapshotInfo>, String> {
+    let mut snapshots: Vec<SnapshotInfo> = fs::read_dir(get_snapshots_dir()?)
+        .map_err(|error| error.to_string())?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let id = path.file_name()?.to_str()?.strip_suffix(".zip")?.to_owned();
+            let manifest = read_manifest(&mut open_archive(&path).ok()?).ok()?;
+            Some(SnapshotInfo {
+                id,
+                manifest,
+                size: entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.manifest.created));
+    Ok(snapshots)
+}
+
+/// The ids of the snapshots, newest first, that are past `settings`' count or age at `now`.
+/// Snapshots in `kept` are never expired and don't count towards the limit.
+fn select_expired(
+    snapshots: &[SnapshotInfo],
+    settings: &SnapshotSettings,
+    kept: &HashSet<String>,
+    now: u64,
+) -> Vec<String> {
+    snapshots
+        .iter()
+        .filter(|snapshot| !kept.contains(&snapshot.id))
+        .enumerate()
+        .filter(|(i, snapshot)| {
+            *i >= settings.max_count
+                || settings.max_age_days.is_some_and(|days| {
+                    now.saturating_sub(snapshot.manifest.created) > days * 24 * 60 * 60
+                })
+        })
+        .map(|(_, snapshot)| snapshot.id.clone())
+        .collect()
+}
+
+/// Deletes the snapshots that are past `settings`' count or age. Snapshots in `kept` are never
+/// deleted and don't count towards the limit.
+pub fn apply_retention(
+    settings: &SnapshotSettings,
+    kept: &HashSet<String>,
+) -> Result<Vec<String>, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let snapshots_dir = get_snapshots_dir()?;
+
+    let expired = select_expired(&list()?, settings, kept, now);
+    for id in &expired {
+        fs::remove_file(snapshots_dir.join(format!("{id}.zip")))
+            .map_err(|error| error.to_string())?;
+    }
+    Ok(expired)
+}
+
+/// The CRC-32 of every file in a snapshot, by path relative to `WTF`.
+fn get_archive_checksums(archive: &mut ZipArchive<File>) -> Result<BTreeMap<String, u32>, String> {
+    let mut checksums = BTreeMap::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|error| error.to_string())?;
+        if !file.is_dir() && file.name() != MANIFEST_NAME {
+            checksums.insert(file.name().to_owned(), file.crc32());
+        }
+    }
+    Ok(checksums)
+}
+
+fn get_file_checksums(files: &BTreeMap<String, PathBuf>) -> BTreeMap<String, u32> {
+    files
+        .iter()
+        .filter_map(|(name, path)| Some((name.clone(), crc32fast::hash(&fs::read(path).ok()?))))
+        .collect()
+}
+
+/// Compares snapshot `id` with snapshot `other`, or with the files on disk right now.
+pub fn diff(wtf_path: &Path, id: &str, other: Option<&str>) -> Result<SnapshotDiff, String> {
+    let mut archive = open_archive(&get_snapshot_path(id)?)?;
+    let manifest = read_manifest(&mut archive)?;
+    let before = get_archive_checksums(&mut archive)?;
+    let after = match other {
+        Some(other) => get_archive_checksums(&mut open_archive(&get_snapshot_path(other)?)?)?,
+        None => get_file_checksums(&select_files(
+            wtf_path,
+            manifest.folders.as_deref(),
+            manifest.files.as_deref(),
+        )),
+    };
+
+    let mut diff = SnapshotDiff::default();
+    for (name, checksum) in &before {
+        match after.get(name) {
+            Some(other_checksum) if other_checksum != checksum => diff.changed.push(name.clone()),
+            Some(_) => {}
+            None => diff.removed.push(name.clone()),
+        }
+    }
+    diff.added = after
+        .into_keys()
+        .filter(|name| !before.contains_key(name))
+        .collect();
+    Ok(diff)
+}
+
+/// Writes the files of snapshot `id` back into `wtf_path`. The files it would overwrite are
+/// snapshotted first, so a restore can be undone. Files that didn't exist when the snapshot
+/// was taken are left alone.
+pub fn restore(wtf_path: &Path, id: &str) -> Result<SnapshotInfo, String> {
+    let mut archive = open_archive(&get_snapshot_path(id)?)?;
+    let manifest = read_manifest(&mut archive)?;
+    let backup = create_with(
+        wtf_path,
+        manifest.release_type,
+        format!("Before restoring '{}'", manifest.label),
+        manifest.folders.clone(),
+        manifest.files.clone(),
+    )?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|error| error.to_string())?;
+        let Some(path) = file.enclosed_name().map(PathBuf::from) else {
+            continue;
+        };
+        if file.is_dir() || path == Path::new(MANIFEST_NAME) {
+            continue;
+        }
+
+        let output_path = wtf_path.join(path);
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|error| error.to_string())?;
+        crate::data::replace_atomically(&output_path, &contents)
+            .map_err(|error| error.to_string())?;
+    }
+    Ok(backup)
+}
+
+/// Applies the retention limits from the config. The snapshots that managed addons keep for
+/// going back to an earlier version are kept, so this locks the addons.
+pub fn apply_configured_retention(state: &AppState) -> Result<Vec<String>, String> {
+    apply_configured_retention_keeping(state, &[])
+}
+
+/// Like `apply_configured_retention`, but also keeps `ids`, e.g. the snapshot taken to undo a
+/// change that was just made.
+pub fn apply_configured_retention_keeping(
+    state: &AppState,
+    ids: &[&str],
+) -> Result<Vec<String>, String> {
+    let settings = match state.config.lock() {
+        Ok(config) => config.get_snapshot_settings().clone(),
+        Err(error) => return Err(error.to_string()),
+    };
+    let mut kept: HashSet<String> = match state.addons.lock() {
+        Ok(addons) => ReleaseType::ALL
+            .into_iter()
+            .flat_map(|release_type| addons.get_list(release_type))
+            .flat_map(|addon| &addon.settings_snapshots)
+            .map(|snapshot| snapshot.snapshot_id.clone())
+            .collect(),
+        Err(error) => return Err(error.to_string()),
+    };
+    kept.extend(ids.iter().map(|id| id.to_string()));
+    apply_retention(&settings, &kept)
+}
+
+/// Snapshots the SavedVariables of `folders`, or the whole WTF folder if none are given.
+#[tauri::command]
+pub fn create_snapshot(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    label: String,
+    folders: Option<Vec<String>>,
+) -> Result<SnapshotInfo, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    let snapshot = create(&wtf_path, release_type, label, folders)?;
+    apply_configured_retention(&state)?;
+    Ok(snapshot)
+}
+
+#[tauri::command]
+pub fn list_snapshots(release_type: ReleaseType) -> Result<Vec<SnapshotInfo>, String> {
+    let mut snapshots = list()?;
+    snapshots.retain(|snapshot| snapshot.manifest.release_type == release_type);
+    Ok(snapshots)
+}
+
+#[tauri::command]
+pub fn diff_snapshot(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    id: String,
+    other: Option<String>,
+) -> Result<SnapshotDiff, String> {
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    diff(&wtf_path, &id, other.as_deref())
+}
+
+/// Restores snapshot `id`, returning the snapshot that was taken of the files it replaced.
+#[tauri::command]
+pub fn restore_snapshot(
+    state: State<AppState>,
+    release_type: ReleaseType,
+    id: String,
+) -> Result<SnapshotInfo, String> {
+    ensure_game_closed()?;
+    let wtf_path = get_configured_wtf_path(&state, release_type)?;
+    restore(&wtf_path, &id)
+}
+
+#[tauri::command]
+pub fn delete_snapshot(id: String) -> Result<(), String> {
+    fs::remove_file(get_snapshot_path(&id)?).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{data::config::SnapshotSettings, game::ReleaseType};
+
+    use super::{select_expired, SnapshotInfo, SnapshotManifest};
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 100 * DAY;
+
+    /// Snapshots taken `ages` days ago, newest first like `list` returns them.
+    fn snapshots(ages: &[u64]) -> Vec<SnapshotInfo> {
+        ages.iter()
+            .map(|age| SnapshotInfo {
+                id: format!("{age}"),
+                manifest: SnapshotManifest {
+                    label: format!("{age} days ago"),
+                    created: NOW - age * DAY,
+                    release_type: ReleaseType::Retail,
+                    folders: None,
+                    files: None,
+                },
+                size: 0,
+            })
+            .collect()
+    }
+
+    fn kept(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn count_keeps_the_newest() {
+        let settings = SnapshotSettings {
+            max_count: 2,
+            max_age_days: None,
+        };
+
+        let expired = select_expired(&snapshots(&[1, 2, 3, 4]), &settings, &kept(&[]), NOW);
+
+        assert_eq!(expired, ["3", "4"]);
+    }
+
+    #[test]
+    fn age_expires_old_snapshots() {
+        let settings = SnapshotSettings {
+            max_count: 20,
+            max_age_days: Some(30),
+        };
+
+        let expired = select_expired(&snapshots(&[1, 30, 31, 90]), &settings, &kept(&[]), NOW);
+
+        assert_eq!(expired, ["31", "90"]);
+    }
+
+    #[test]
+    fn kept_snapshots_are_never_expired() {
+        let settings = SnapshotSettings {
+            max_count: 1,
+            max_age_days: Some(30),
+        };
+
+        let expired = select_expired(
+            &snapshots(&[1, 2, 3, 90]),
+            &settings,
+            &kept(&["1", "90"]),
+            NOW,
+        );
+
+        // "1" doesn't count towards the limit, so "2" is the one snapshot left to keep.
+        assert_eq!(expired, ["3"]);
+    }
+
+    #[test]
+    fn nothing_expires_within_the_limits() {
+        let settings = SnapshotSettings {
+            max_count: 20,
+            max_age_days: None,
+        };
+
+        assert!(select_expired(&snapshots(&[1, 99]), &settings, &kept(&[]), NOW).is_empty());
+    }
+}